@@ -1,102 +1,144 @@
-extern crate tree_magic;
 
 use std::path::Path;
 
-use clap::{Arg, ArgAction, Command};
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
 
-use crate::module_manager::{ArgumentList, ArgumentResult};
-use crate::{printer::{raise_error, print_dir_not_empty_warning}, helper::get_automatic_path};
+use crate::module_manager::{Argument, ArgumentList, ArgumentResult, Renderer};
+use crate::probe::{self, MediaInfo};
+use crate::review::check_review_path;
+use crate::subtitles::{check_retimed_path, is_subtitle_file};
+use crate::timeline::parse_timestamp;
+use crate::{printer::{raise_error, print_dir_not_empty_warning}, helper::{get_automatic_path, get_path_in_dir, parse_input_list, read_input_list, relative_parent, validate_templates, AUDIO_FORMATS, DEFAULT_NAME_TEMPLATE}};
+
+#[derive(Clone)]
+pub enum Mode {
+  Process,
+  Watch {
+    archive: String,
+    settle: u64,
+  },
+  Serve {
+    bind: String,
+  },
+  Queue(QueueAction),
+  /// Converts a timestamp with a timestamp map written by `--timestamp-map`.
+  Map {
+    time: f64,
+    to_input: bool,
+  },
+  /// Lets the user edit the cuts in the terminal before rendering.
+  Review,
+  /// Compares the cut list in `input` with `other`.
+  Diff {
+    other: String,
+  },
+  /// Compares the generated cuts with hand-labelled speech regions.
+  Eval {
+    truth: String,
+  },
+  /// Runs the generator for every combination of the given generator argument values.
+  Sweep {
+    args: Vec<(String, Vec<String>)>,
+  },
+}
+
+#[derive(Clone)]
+pub enum QueueAction {
+  Add {
+    priority: i64,
+  },
+  List,
+  Cancel {
+    id: u64,
+  },
+  Priority {
+    id: u64,
+    priority: i64,
+  },
+  Run,
+}
 
 #[derive(Clone)]
 pub struct Options {
+  pub mode: Mode,
   pub input: String,
   pub output: String,
   pub tsonly: bool,
+  pub no_cache: bool,
+  pub pcm_rate: u32,
+  pub pcm_channels: u16,
+  pub name_template: String,
+  pub output_template: Option<String>,
+  /// Container of the output, overriding the extension of the input.
+  pub format: Option<String>,
+  /// Audio container to produce instead of a video.
+  pub audio_only: Option<String>,
+  /// Minimum removed gap in seconds that starts a new chapter.
+  pub chapters: Option<f64>,
+  pub chapter_format: String,
+  pub timestamp_map: bool,
+  /// SRT or WebVTT file retimed to the output.
+  pub subtitles: Option<String>,
+  /// HTML page to write for reviewing the cuts.
+  pub review_html: Option<String>,
+  /// Only generate and show the cuts, without writing anything.
+  pub dry_run: bool,
+  /// Per-file overrides for directory mode.
+  pub manifest: Option<String>,
+  /// File listing the inputs to process, one per line. `-` reads the list from stdin.
+  pub input_list: Option<String>,
+  /// Inputs of the input list with their outputs, resolved by `validate_args`.
+  pub inputs: Vec<(String, String)>,
+  /// What is known about a single input file, probed by `validate_args`.
+  pub info: Option<MediaInfo>,
+  /// Seconds always removed from the start and the end of the input.
+  pub trim_start: f64,
+  pub trim_end: f64,
+  /// Name of the generator in use, available as `{generator}` in name templates.
+  pub generator_name: String,
   pub generator_args: Vec<ArgumentResult>,
   pub render_args: Vec<ArgumentResult>,
 }
 
-pub fn parse_args(generator_args: ArgumentList, render_args: ArgumentList) -> Options {
-  let mut options: Options = Options {
-    input: String::new(),
-    output: String::new(),
-    tsonly: false,
-    generator_args: Vec::new(),
-    render_args: Vec::new(),
-  };
-
-  {
-    // ensure that generator and render arguments are not overlapping
-    let mut longs = Vec::new();
-    let mut shorts = Vec::new();
-    for arg in &generator_args {
-      if longs.contains(&arg.long) || shorts.contains(&arg.short) {
-        raise_error("Argument names are overlapping.");
-      }
-      longs.push(arg.long.clone());
-      shorts.push(arg.short);
-    }
-    for arg in &render_args {
-      if longs.contains(&arg.long) || shorts.contains(&arg.short) {
-        raise_error("Argument names are overlapping.");
-      }
-      longs.push(arg.long.clone());
-      shorts.push(arg.short);
-    }
+impl Options {
+  /// The container requested with `--audio-only` or `--format`, if any.
+  pub fn container(&self) -> Option<&str> {
+    self.audio_only.as_deref().or(self.format.as_deref())
   }
+}
 
-  let mut command = Command::new("lecturecut")
-    .about("LectureCut is a tool to remove silence from videos.
-
-    It uses WebRTC's VAD to detect silence and ffmpeg to transcode the video.
-    To speed up transcoding, a form of smart encoding is employed. This means
-    that the video is split into segments and only the segments that need to be
-    cut are transcoded. This results in a much faster transcoding process, but
-    the output video will have a slightly lower quality than the input video.")
-    .arg(Arg::new("input").short('i').long("input").help("The video file to process").required(true))
-    .arg(Arg::new("output").short('o').long("output").help("The output file. If not specified, LectureCut will automatically generate a name."))
-    .arg(Arg::new("tsonly").long("tsonly").help("Only output the timestamps of the cuts. This is useful for debugging purposes or if you want to use the cuts in another program.").action(ArgAction::SetTrue));
-
+fn add_module_args(mut command: Command, generator_args: &ArgumentList, render_args: &ArgumentList) -> Command {
   command = command.next_help_heading("Generator Arguments");
-  for arg in &generator_args {
-    let mut arrg = Arg::new(arg.long.clone()).long(arg.long.clone()).help(arg.description.clone()).required(arg.required);
-    if arg.short != '\0' {
-      arrg = arrg.short(arg.short);
-    }
-    if arg.is_flag {
-      arrg = arrg.action(ArgAction::SetTrue);
-    }
-    command = command.arg(arrg);
+  for arg in generator_args {
+    command = command.arg(module_arg(arg));
   }
 
   command = command.next_help_heading("Render Arguments");
-  for arg in &render_args {
-    let mut arrg = Arg::new(arg.long.clone()).long(arg.long.clone()).help(arg.description.clone()).required(arg.required);
-    if arg.short != '\0' {
-      arrg = arrg.short(arg.short);
-    }
-    if arg.is_flag {
-      arrg = arrg.action(ArgAction::SetTrue);
-    }
-    command = command.arg(arrg);
+  for arg in render_args {
+    command = command.arg(module_arg(arg));
   }
 
-  // parse arguments
-  let matches = command.get_matches();
+  command
+}
 
-  // unpack arguments
-  options.input = matches.get_one::<String>("input").unwrap().to_string();
-  if let Some(output) = matches.get_one::<String>("output") {
-    options.output = output.to_string();
+fn module_arg(arg: &Argument) -> Arg {
+  let mut arrg = Arg::new(arg.long.clone()).long(arg.long.clone()).help(arg.description.clone()).required(arg.required);
+  if arg.short != '\0' {
+    arrg = arrg.short(arg.short);
   }
-  options.tsonly = matches.get_flag("tsonly");
+  if arg.is_flag {
+    arrg = arrg.action(ArgAction::SetTrue);
+  }
+  arrg
+}
 
-  // unpack generator arguments
-  for arg in &generator_args {
+fn unpack_module_args(matches: &ArgMatches, args: &ArgumentList) -> Vec<ArgumentResult> {
+  let mut results = Vec::new();
+  for arg in args {
     match arg.is_flag {
       true => {
         if matches.get_flag(&arg.long) {
-          options.generator_args.push(ArgumentResult {
+          results.push(ArgumentResult {
             long: arg.long.clone(),
             value: "true".to_string(),
           });
@@ -104,7 +146,7 @@ pub fn parse_args(generator_args: ArgumentList, render_args: ArgumentList) -> Op
       },
       false => {
         if let Some(value) = matches.get_one::<String>(&arg.long) {
-          options.generator_args.push(ArgumentResult {
+          results.push(ArgumentResult {
             long: arg.long.clone(),
             value: value.to_string(),
           });
@@ -112,15 +154,284 @@ pub fn parse_args(generator_args: ArgumentList, render_args: ArgumentList) -> Op
       }
     }
   }
+  results
+}
+
+/// Returns the generator selected with `--generator`.
+/// It is needed before the actual parsing, as the generator provides part of the arguments.
+pub fn selected_generator() -> String {
+  let args: Vec<String> = std::env::args().collect();
+  for (i, arg) in args.iter().enumerate() {
+    if let Some(value) = arg.strip_prefix("--generator=") {
+      return value.to_string();
+    }
+    if arg == "--generator" {
+      if let Some(value) = args.get(i + 1) {
+        return value.to_string();
+      }
+    }
+  }
+  "module".to_string()
+}
+
+fn build_command(generator_args: &ArgumentList, render_args: &ArgumentList) -> Command {
+  let watch_command = Command::new("watch")
+    .about("Watch a directory and process new videos as soon as they are fully written.")
+    .arg(Arg::new("dir").help("The directory to watch").required(true))
+    .arg(Arg::new("output").short('o').long("output").help("The directory processed files are written to. Defaults to <dir>/lecturecut."))
+    .arg(Arg::new("archive").long("archive").help("The directory originals are moved to once processed. Defaults to <dir>/archive."))
+    .arg(Arg::new("settle").long("settle").help("Seconds a file size has to stay unchanged before the file is considered fully written.").value_parser(value_parser!(u64)).default_value("10"));
+
+  let serve_command = Command::new("serve")
+    .about("Serve a local HTTP API to queue jobs and fetch their cuts and stats.")
+    .arg(Arg::new("bind").long("bind").help("The address to listen on").default_value("127.0.0.1:8080"));
+
+  let job_id_arg = Arg::new("id").help("The id of the job").required(true).value_parser(value_parser!(u64));
+  let queue_command = Command::new("queue")
+    .about("Manage the persistent job queue.")
+    .subcommand_required(true)
+    .subcommand(add_module_args(Command::new("add")
+      .about("Add a video file to the queue.")
+      .arg(Arg::new("input").help("The video file to process").required(true))
+      .arg(Arg::new("output").short('o').long("output").help("The output file. If not specified, LectureCut will automatically generate a name."))
+      .arg(Arg::new("priority").long("priority").help("Jobs with a higher priority are processed first.").value_parser(value_parser!(i64)).allow_negative_numbers(true).default_value("0")),
+      generator_args, render_args))
+    .subcommand(Command::new("list").about("List all jobs in the queue."))
+    .subcommand(Command::new("cancel").about("Cancel a queued job.").arg(job_id_arg.clone()))
+    .subcommand(Command::new("priority")
+      .about("Change the priority of a queued job.")
+      .arg(job_id_arg)
+      .arg(Arg::new("priority").help("The new priority").required(true).value_parser(value_parser!(i64)).allow_negative_numbers(true)))
+    .subcommand(Command::new("run").about("Process queued jobs until the queue is empty."));
+
+  let review_command = Command::new("review")
+    .about("Generate the cuts, restore removed gaps or adjust the padding in the terminal and render the result.")
+    .arg(Arg::new("input").help("The video file to process").required(true))
+    .arg(Arg::new("output").short('o').long("output").help("The output file. If not specified, LectureCut will automatically generate a name."));
+
+  let diff_command = Command::new("diff")
+    .about("Compare two cut lists written with --tsonly, e.g. of two generator versions.")
+    .arg(Arg::new("before").help("The first cut list").required(true))
+    .arg(Arg::new("after").help("The cut list compared to the first one").required(true));
+
+  let eval_command = Command::new("eval")
+    .about("Run the generator and compare the kept parts with hand-labelled speech regions.")
+    .arg(Arg::new("input").help("The video file to process").required(true))
+    .arg(Arg::new("truth").long("truth").help("CSV file with one labelled speech region (start,end in seconds) per line").required(true));
+
+  let sweep_command = Command::new("sweep")
+    .about("Run the generator for every combination of generator argument values and compare the results.")
+    .arg(Arg::new("input").help("The video file to process").required(true))
+    .arg(Arg::new("arg").long("arg").help("A generator argument and the values to try, e.g. aggressiveness=0,1,2. Can be given multiple times.").value_name("NAME=VALUES").action(ArgAction::Append).required(true));
+
+  let map_command = Command::new("map")
+    .about("Convert a timestamp between the input and the output using a timestamp map.")
+    .arg(Arg::new("map").help("The timestamp map written with --timestamp-map").required(true))
+    .arg(Arg::new("time").long("time").help("The timestamp to convert, e.g. 34:10 or 1:02:03.5").required(true))
+    .arg(Arg::new("to").long("to").help("The timeline to convert to. Input timestamps are converted to the output by default.").value_parser(["output", "input"]).default_value("output"));
+
+  let command = Command::new("lecturecut")
+    .about("LectureCut is a tool to remove silence from videos.
+
+    It uses WebRTC's VAD to detect silence and ffmpeg to transcode the video.
+    To speed up transcoding, a form of smart encoding is employed. This means
+    that the video is split into segments and only the segments that need to be
+    cut are transcoded. This results in a much faster transcoding process, but
+    the output video will have a slightly lower quality than the input video.")
+    .subcommand_negates_reqs(true)
+    .arg(Arg::new("input").short('i').long("input").help("The video file to process. Use - to read WAV or raw PCM audio from stdin.").required_unless_present("input-list"))
+    .arg(Arg::new("input-list").long("input-list").help("Process every file listed in FILE, one per line and optionally followed by a tab and its output. Use - to read the list from stdin. Below -o, the folders of relative inputs are kept.").value_name("FILE").conflicts_with("input"))
+    .arg(Arg::new("output").short('o').long("output").help("The output file. If not specified, LectureCut will automatically generate a name."))
+    .arg(Arg::new("pcm-rate").long("pcm-rate").help("Sample rate of raw 16 bit PCM audio piped into stdin").value_parser(value_parser!(u32).range(1..)).default_value("16000"))
+    .arg(Arg::new("pcm-channels").long("pcm-channels").help("Channel count of raw 16 bit PCM audio piped into stdin").value_parser(value_parser!(u16).range(1..)).default_value("1"))
+    .arg(Arg::new("tsonly").long("tsonly").help("Only output the timestamps of the cuts. This is useful for debugging purposes or if you want to use the cuts in another program.").action(ArgAction::SetTrue).global(true))
+    .arg(Arg::new("generator").long("generator").help("The generator to use. \"module\" loads the compiled generator module and falls back to \"builtin-energy\", a simple loudness based generator, if it is missing.").value_parser(["module", "builtin-energy"]).default_value("module").global(true))
+    .arg(Arg::new("name-template").long("name-template").help("Name of automatically named output files. Available placeholders: {stem}, {suffix}, {ext}, {date}, {generator}").default_value(DEFAULT_NAME_TEMPLATE).global(true))
+    .arg(Arg::new("output-template").long("output-template").help("Path of automatically named output files, e.g. \"{dir}/{stem}.cut.{ext}\". {dir} is the directory of the input, or the output directory when processing a directory. Also available: {stem}, {suffix}, {ext}, {date}, {generator}, {preset}").global(true))
+    .arg(Arg::new("format").long("format").help("The container of the output video").value_parser(["mkv", "mp4", "webm"]).global(true))
+    .arg(Arg::new("audio-only").long("audio-only").help("Only keep the audio, e.g. for podcasts. Needs a render module supporting it.").value_name("FORMAT").value_parser(AUDIO_FORMATS).num_args(0..=1).default_missing_value("m4a").conflicts_with_all(["format", "tsonly"]).global(true))
+    .arg(Arg::new("chapters").long("chapters").help("Start a new chapter after every removed gap longer than SECONDS and write the chapters next to the output.").value_name("SECONDS").value_parser(value_parser!(f64)).global(true))
+    .arg(Arg::new("chapter-format").long("chapter-format").help("Format of the chapters file: WebVTT or ffmpeg metadata").value_parser(["vtt", "ffmetadata"]).default_value("vtt").global(true))
+    .arg(Arg::new("timestamp-map").long("timestamp-map").help("Write a file next to the output mapping its timestamps to the input, see the map command.").action(ArgAction::SetTrue).global(true))
+    .arg(Arg::new("subtitles").long("subtitles").help("SRT or WebVTT subtitles of the input. They are retimed to the cut video and written next to the output.").value_name("FILE"))
+    .arg(Arg::new("review-html").long("review-html").help("Write a self-contained HTML page with a timeline of the kept and removed parts and a player of the input, for reviewing the cuts.").value_name("FILE"))
+    .arg(Arg::new("dry-run").long("dry-run").help("Only generate the cuts and show them on a timeline, without rendering or writing any files.").action(ArgAction::SetTrue))
+    .arg(Arg::new("manifest").long("manifest").help("CSV or TOML file with per-file overrides (output name, trim_start, trim_end and module arguments) when processing a directory").value_name("FILE"))
+    .arg(Arg::new("no-cache").long("no-cache").help("Always run the generator instead of reusing cached cuts from a previous run with the same input and generator arguments.").action(ArgAction::SetTrue).global(true))
+    .subcommand(add_module_args(watch_command, generator_args, render_args))
+    .subcommand(serve_command)
+    .subcommand(queue_command)
+    .subcommand(add_module_args(review_command, generator_args, render_args))
+    .subcommand(map_command)
+    .subcommand(diff_command)
+    .subcommand(add_module_args(eval_command, generator_args, render_args))
+    .subcommand(add_module_args(sweep_command, generator_args, render_args));
+
 
-  // unpack render arguments
-  for arg in &render_args {
-    if let Some(value) = matches.get_one::<String>(&arg.long) {
-      options.render_args.push(ArgumentResult {
-        long: arg.long.clone(),
-        value: value.to_string(),
-      });
+  add_module_args(command, generator_args, render_args)
+}
+
+fn options_from_matches(matches: &ArgMatches, generator_name: &str, generator_args: &ArgumentList, render_args: &ArgumentList) -> Options {
+  let mut options: Options = Options {
+    mode: Mode::Process,
+    input: String::new(),
+    output: String::new(),
+    tsonly: false,
+    no_cache: false,
+    pcm_rate: 16000,
+    pcm_channels: 1,
+    name_template: DEFAULT_NAME_TEMPLATE.to_string(),
+    output_template: None,
+    format: None,
+    audio_only: None,
+    chapters: None,
+    chapter_format: "vtt".to_string(),
+    timestamp_map: false,
+    subtitles: None,
+    review_html: None,
+    dry_run: false,
+    manifest: None,
+    input_list: None,
+    inputs: Vec::new(),
+    info: None,
+    trim_start: 0.0,
+    trim_end: 0.0,
+    generator_name: generator_name.to_string(),
+    generator_args: Vec::new(),
+    render_args: Vec::new(),
+  };
+
+  // unpack arguments
+  options.tsonly = matches.get_flag("tsonly");
+  options.no_cache = matches.get_flag("no-cache");
+  options.name_template = matches.get_one::<String>("name-template").unwrap().to_string();
+  options.output_template = matches.get_one::<String>("output-template").cloned();
+  options.format = matches.get_one::<String>("format").cloned();
+  options.audio_only = matches.get_one::<String>("audio-only").cloned();
+  options.chapters = matches.get_one::<f64>("chapters").copied();
+  options.chapter_format = matches.get_one::<String>("chapter-format").unwrap().to_string();
+  options.timestamp_map = matches.get_flag("timestamp-map");
+
+  let module_matches = match matches.subcommand() {
+    Some(("watch", sub_matches)) => {
+      options.input = sub_matches.get_one::<String>("dir").unwrap().to_string();
+      if let Some(output) = sub_matches.get_one::<String>("output") {
+        options.output = output.to_string();
+      }
+      options.mode = Mode::Watch {
+        archive: sub_matches.get_one::<String>("archive").cloned().unwrap_or_default(),
+        settle: *sub_matches.get_one::<u64>("settle").unwrap(),
+      };
+      Some(sub_matches)
+    },
+    Some(("serve", sub_matches)) => {
+      options.mode = Mode::Serve {
+        bind: sub_matches.get_one::<String>("bind").unwrap().to_string(),
+      };
+      // module arguments are given per job
+      None
+    },
+    Some(("review", sub_matches)) => {
+      options.input = sub_matches.get_one::<String>("input").unwrap().to_string();
+      if let Some(output) = sub_matches.get_one::<String>("output") {
+        options.output = output.to_string();
+      }
+      options.mode = Mode::Review;
+      Some(sub_matches)
+    },
+    Some(("eval", sub_matches)) => {
+      options.input = sub_matches.get_one::<String>("input").unwrap().to_string();
+      options.mode = Mode::Eval {
+        truth: sub_matches.get_one::<String>("truth").unwrap().to_string(),
+      };
+      Some(sub_matches)
+    },
+    Some(("sweep", sub_matches)) => {
+      options.input = sub_matches.get_one::<String>("input").unwrap().to_string();
+      let mut args: Vec<(String, Vec<String>)> = Vec::new();
+      for arg in sub_matches.get_many::<String>("arg").unwrap() {
+        let (name, values) = match arg.split_once('=') {
+          Some((name, values)) => (name.trim_start_matches("--"), values),
+          None => {
+            raise_error(format!("--arg needs to look like name=value1,value2, got {}.", arg).as_str());
+            continue;
+          }
+        };
+        if !generator_args.iter().any(|generator_arg| generator_arg.long == name) {
+          raise_error(format!("The generator has no argument {}.", name).as_str());
+        }
+        if args.iter().any(|(swept, _)| swept == name) {
+          raise_error(format!("{} is swept more than once.", name).as_str());
+        }
+        args.push((name.to_string(), values.split(',').map(|value| value.trim().to_string()).collect()));
+      }
+      options.mode = Mode::Sweep { args };
+      Some(sub_matches)
+    },
+    Some(("diff", sub_matches)) => {
+      options.input = sub_matches.get_one::<String>("before").unwrap().to_string();
+      options.mode = Mode::Diff {
+        other: sub_matches.get_one::<String>("after").unwrap().to_string(),
+      };
+      None
+    },
+    Some(("map", sub_matches)) => {
+      options.input = sub_matches.get_one::<String>("map").unwrap().to_string();
+      let time = sub_matches.get_one::<String>("time").unwrap();
+      options.mode = Mode::Map {
+        time: parse_timestamp(time).unwrap_or_else(|| {
+          raise_error(format!("Invalid timestamp {}.", time).as_str());
+          0.0
+        }),
+        to_input: sub_matches.get_one::<String>("to").unwrap() == "input",
+      };
+      None
+    },
+    Some(("queue", sub_matches)) => {
+      let (action, action_matches) = match sub_matches.subcommand() {
+        Some(("add", add_matches)) => {
+          options.input = add_matches.get_one::<String>("input").unwrap().to_string();
+          if let Some(output) = add_matches.get_one::<String>("output") {
+            options.output = output.to_string();
+          }
+          (QueueAction::Add {
+            priority: *add_matches.get_one::<i64>("priority").unwrap(),
+          }, Some(add_matches))
+        },
+        Some(("cancel", cancel_matches)) => (QueueAction::Cancel {
+          id: *cancel_matches.get_one::<u64>("id").unwrap(),
+        }, None),
+        Some(("priority", priority_matches)) => (QueueAction::Priority {
+          id: *priority_matches.get_one::<u64>("id").unwrap(),
+          priority: *priority_matches.get_one::<i64>("priority").unwrap(),
+        }, None),
+        Some(("run", _)) => (QueueAction::Run, None),
+        _ => (QueueAction::List, None),
+      };
+      options.mode = Mode::Queue(action);
+      action_matches
+    },
+    _ => {
+      if let Some(input) = matches.get_one::<String>("input") {
+        options.input = input.to_string();
+      }
+      options.input_list = matches.get_one::<String>("input-list").cloned();
+      if let Some(output) = matches.get_one::<String>("output") {
+        options.output = output.to_string();
+      }
+      options.pcm_rate = *matches.get_one::<u32>("pcm-rate").unwrap();
+      options.pcm_channels = *matches.get_one::<u16>("pcm-channels").unwrap();
+      options.subtitles = matches.get_one::<String>("subtitles").cloned();
+      options.review_html = matches.get_one::<String>("review-html").cloned();
+      options.dry_run = matches.get_flag("dry-run");
+      options.manifest = matches.get_one::<String>("manifest").cloned();
+      Some(matches)
     }
+  };
+
+  if let Some(module_matches) = module_matches {
+    options.generator_args = unpack_module_args(module_matches, generator_args);
+    options.render_args = unpack_module_args(module_matches, render_args);
   }
 
   // because windows is seemingly designed by a 5 year old
@@ -134,10 +445,214 @@ pub fn parse_args(generator_args: ArgumentList, render_args: ArgumentList) -> Op
   options
 }
 
+/// Parses the subcommands that only read files written earlier, i.e. `map` and `diff`.
+/// They need no module, so they are handled before any module is loaded.
+pub fn parse_offline_args() -> Option<Options> {
+  let matches = build_command(&Vec::new(), &Vec::new()).try_get_matches().ok()?;
+  match matches.subcommand_name() {
+    Some("map") | Some("diff") => Some(options_from_matches(&matches, "", &Vec::new(), &Vec::new())),
+    _ => None,
+  }
+}
+
+pub fn parse_args(generator_name: &str, generator_args: ArgumentList, render_args: ArgumentList) -> Options {
+  {
+    // ensure that generator and render arguments are not overlapping
+    let mut longs = Vec::new();
+    let mut shorts = Vec::new();
+    // '\0' means the argument has no short name
+    for arg in generator_args.iter().chain(render_args.iter()) {
+      if longs.contains(&arg.long) || (arg.short != '\0' && shorts.contains(&arg.short)) {
+        raise_error("Argument names are overlapping.");
+      }
+      longs.push(arg.long.clone());
+      shorts.push(arg.short);
+    }
+  }
+
+  let matches = build_command(&generator_args, &render_args).get_matches();
+  // map and diff parse without module arguments, so they only get here when some were given
+  if let Some("map") | Some("diff") = matches.subcommand_name() {
+    raise_error("map and diff take no module arguments.");
+  }
+  options_from_matches(&matches, generator_name, &generator_args, &render_args)
+}
+
+/// Asks the render module whether it can turn `input` into `output`, whose extension names the output format.
+pub fn check_render_support(render: &dyn Renderer, input: &Path, info: &MediaInfo, output: &Path) -> Result<(), String> {
+  let output_format = output.extension().unwrap_or_default().to_string_lossy();
+  let codecs = info.codecs();
+  if !render.supports(info.container.as_str(), &codecs, output_format.as_ref()) {
+    let streams = if codecs.is_empty() { String::new() } else { format!(" with {}", codecs.join(", ")) };
+    return Err(format!("The render module can not turn {} ({}{}) into a .{} file.", input.display(), info.container, streams, output_format));
+  }
+  Ok(())
+}
+
+fn check_output_chars(output: &str) {
+  // may not contain any illegal characters for paths
+  // if is windows
+  let mut illegal_chars: String = "".to_string();
+  if cfg!(windows) {
+    illegal_chars += r#"<>"|?*"#;
+    for i in 0..32 {
+      illegal_chars += &(i as u8 as char).to_string();
+    }
+  } else {
+    illegal_chars = (0 as char).to_string();
+  }
+  for c in illegal_chars.chars() {
+    if output.contains(c) {
+      println!("Output path contains illegal characters. ({})", c);
+      
+      raise_error("Output path contains illegal characters.");
+    }
+  }
+}
+
+/// Resolves the output of every entry of an input list.
+/// Missing inputs and existing outputs are skipped with a warning, so the rest of the list is still processed.
+/// Checks the output of a file of an input list or a manifest directory.
+/// `earlier` holds the inputs and outputs of the files before it, `is_explicit` whether the output was given.
+pub fn check_listed_output(options: &Options, input: &str, output: &str, is_explicit: bool, earlier: &[(String, String)]) {
+  check_output_chars(output);
+  if let (Some(container), true, false) = (options.container(), is_explicit, options.tsonly) {
+    if Path::new(output).extension().is_none_or(|ext| ext != container) {
+      raise_error(format!("The output {} of {} needs the .{} extension to match --format or --audio-only.", output, input, container).as_str());
+    }
+  }
+  if let Some((other, _)) = earlier.iter().find(|(_, other_output)| Path::new(other_output) == Path::new(output)) {
+    raise_error(format!("{} and {} would both be written to {}.", other, input, output).as_str());
+  }
+}
+
+fn resolve_input_list(options: &Options, content: &str) -> Vec<(String, String)> {
+  let output_dir = Path::new(options.output.as_str());
+  let mut entries: Vec<(String, String)> = Vec::new();
+  for (input, output) in parse_input_list(content) {
+    let file = Path::new(input.as_str());
+    if !file.is_file() {
+      eprintln!("Warning: Skipping {}, it is not a file.", input);
+      continue;
+    }
+    let is_explicit = output.is_some();
+    let output = match (output, options.output.is_empty()) {
+      (Some(output), true) => output,
+      (Some(output), false) => output_dir.join(output).to_string_lossy().to_string(),
+      (None, true) => get_automatic_path(input.as_str(), None, options),
+      // the folders of the inputs are kept, so files with the same name do not end up in the same place
+      (None, false) => get_path_in_dir(file, &output_dir.join(relative_parent(file)), options),
+    };
+
+    if Path::new(output.as_str()).exists() {
+      eprintln!("Warning: Skipping {}, {} already exists.", input, output);
+      continue;
+    }
+    check_listed_output(options, input.as_str(), output.as_str(), is_explicit, &entries);
+    entries.push((input, output));
+  }
+  entries
+}
 
-pub fn validate_args(options: Options) -> Options {
+pub fn validate_args(options: Options, render: &dyn Renderer) -> Options {
   let mut changed_options = options.clone();
 
+  if let Err(e) = validate_templates(&options) {
+    raise_error(e.as_str());
+  }
+
+  match &options.mode {
+    // jobs are validated individually when they are submitted
+    Mode::Serve { .. } => return changed_options,
+    Mode::Queue(QueueAction::Add { .. }) if !Path::new(options.input.as_str()).is_file() => {
+      raise_error("Only files can be added to the queue.");
+    },
+    Mode::Queue(QueueAction::Add { .. }) => {},
+    Mode::Queue(_) => return changed_options,
+    Mode::Eval { truth } if !Path::new(options.input.as_str()).is_file() || !Path::new(truth.as_str()).is_file() => {
+      raise_error("The input and the labels need to exist.");
+    },
+    Mode::Eval { .. } => return changed_options,
+    Mode::Sweep { .. } if !Path::new(options.input.as_str()).is_file() => {
+      raise_error("Input file does not exist.");
+    },
+    Mode::Sweep { .. } => return changed_options,
+    Mode::Review if !Path::new(options.input.as_str()).is_file() => {
+      raise_error("Only single files can be reviewed.");
+    },
+    _ => {},
+  }
+
+  if let Some(input_list) = &options.input_list {
+    if input_list != "-" && !Path::new(input_list.as_str()).is_file() {
+      raise_error("Input list does not exist.");
+    }
+    if options.subtitles.is_some() || options.review_html.is_some() || options.manifest.is_some() {
+      raise_error("Subtitles, review pages and manifests can not be used with an input list.");
+    }
+    if !options.output.is_empty() {
+      check_output_chars(options.output.as_str());
+      if Path::new(options.output.as_str()).is_file() {
+        raise_error("The output needs to be a directory when processing an input list.");
+      }
+      if !options.dry_run && std::fs::create_dir_all(options.output.as_str()).is_err() {
+        raise_error("Could not create output directory.");
+      }
+    }
+    let content = read_input_list(input_list).unwrap_or_else(|e| {
+      raise_error(format!("Could not read the input list: {}", e).as_str());
+      String::new()
+    });
+    changed_options.inputs = resolve_input_list(&options, content.as_str());
+    return changed_options;
+  }
+
+  if let Some(subtitles) = &options.subtitles {
+    if !Path::new(subtitles.as_str()).is_file() {
+      raise_error("Subtitle file does not exist.");
+    }
+    if !is_subtitle_file(Path::new(subtitles.as_str())) {
+      raise_error("Subtitles need to be SRT or WebVTT files.");
+    }
+    if Path::new(options.input.as_str()).is_dir() {
+      raise_error("Subtitles can only be retimed for a single input file.");
+    }
+  }
+
+  if let Some(manifest) = &options.manifest {
+    if !Path::new(options.input.as_str()).is_dir() {
+      raise_error("A manifest can only be used when processing a directory.");
+    }
+    if !Path::new(manifest.as_str()).is_file() {
+      raise_error("Manifest file does not exist.");
+    }
+  }
+
+  if options.review_html.is_some() && Path::new(options.input.as_str()).is_dir() {
+    raise_error("A review page can only be written for a single input file.");
+  }
+
+  // audio piped into stdin is only read once processing starts
+  if options.input == "-" {
+    if options.output.is_empty() {
+      raise_error("Reading from stdin needs an output file (-o).");
+    }
+    if Path::new(options.output.as_str()).exists() {
+      raise_error("Output file already exists.");
+    }
+    if !options.tsonly {
+      if let Err(e) = check_render_support(render, Path::new("stdin"), &MediaInfo::stdin_audio(), Path::new(options.output.as_str())) {
+        raise_error(e.as_str());
+      }
+    }
+    if let Some(review_html) = &options.review_html {
+      if let Err(e) = check_review_path(Path::new(review_html.as_str()), Path::new("-"), Path::new(options.output.as_str())) {
+        raise_error(e.as_str());
+      }
+    }
+    return changed_options;
+  }
+
   // input validation
   let input_path: &Path = Path::new(options.input.as_str());
   let input_is_file: bool = input_path.is_file();
@@ -150,39 +665,44 @@ pub fn validate_args(options: Options) -> Options {
   if !input_is_file && !input_path.is_dir() {
     raise_error("Input needs to be a file or a directory.");
   }
-  // check filetype using magicbytes if file
-  if input_is_file {
-    let filetype: String = tree_magic::from_filepath(input_path);
-    // if not video, warn user
-    if !filetype.starts_with("video") {
-      println!("Warning: Input file is not a video file.");
-    }
-  }
+  // files without video are reported once processing starts
+  changed_options.info = if input_is_file { Some(probe::probe(input_path)) } else { None };
 
   if cfg!(windows) {
     changed_options.input = options.input.replace('/', "\\");
   }
 
-  // output validation
-  if !options.output.is_empty() {
-    // may not contain any illegal characters for paths
-    // if is windows
-    let mut illegal_chars: String = "".to_string();
-    if cfg!(windows) {
-      illegal_chars += r#"<>"|?*"#;
-      for i in 0..32 {
-        illegal_chars += &(i as u8 as char).to_string();
-      }
-    } else {
-      illegal_chars = (0 as char).to_string();
+  if let Mode::Watch { archive, settle } = &options.mode {
+    if !input_is_dir {
+      raise_error("The watched path needs to be a directory.");
     }
-    for c in illegal_chars.chars() {
-      if options.output.contains(c) {
-        println!("Output path contains illegal characters. ({})", c);
-        
-        raise_error("Output path contains illegal characters.");
+    changed_options.output = if options.output.is_empty() {
+      input_path.join("lecturecut").to_string_lossy().to_string()
+    } else {
+      options.output.clone()
+    };
+    let archive = if archive.is_empty() {
+      input_path.join("archive").to_string_lossy().to_string()
+    } else {
+      archive.clone()
+    };
+    for dir in [&changed_options.output, &archive] {
+      if std::fs::create_dir_all(dir).is_err() {
+        raise_error(format!("Could not create directory {}.", dir).as_str());
       }
     }
+    changed_options.mode = Mode::Watch { archive, settle: *settle };
+    return changed_options;
+  }
+
+  // nothing is written in a dry run
+  if options.dry_run {
+    return changed_options;
+  }
+
+  // output validation
+  if !options.output.is_empty() {
+    check_output_chars(options.output.as_str());
     let output_path: &Path = Path::new(options.output.as_str());
     if input_is_dir {
       // if input is directory, output must be directory
@@ -210,14 +730,49 @@ pub fn validate_args(options: Options) -> Options {
       if output_path.exists() {
         raise_error("Output file already exists.")
       }
+      if let Some(container) = options.container() {
+        if !options.tsonly && output_path.extension().is_none_or(|ext| ext != container) {
+          raise_error(format!("The output file needs the .{} extension to match --format or --audio-only.", container).as_str());
+        }
+      }
     }
   }
   else if !input_is_dir {
-    changed_options.output = get_automatic_path(options.input.as_str(), options.tsonly);
+    changed_options.output = get_automatic_path(options.input.as_str(), None, &options);
     if Path::new(changed_options.output.as_str()).exists() {
       raise_error("Output file already exists.")
     }
   }
 
+  // incompatible inputs are reported now instead of failing halfway through,
+  // the files of a directory are checked once their outputs are known
+  if let (Some(info), false) = (&changed_options.info, options.tsonly) {
+    if let Err(e) = check_render_support(render, input_path, info, Path::new(changed_options.output.as_str())) {
+      raise_error(e.as_str());
+    }
+  }
+
+  if let Some(subtitles) = &options.subtitles {
+    if let Err(e) = check_retimed_path(Path::new(subtitles.as_str()), Path::new(changed_options.output.as_str())) {
+      raise_error(e.as_str());
+    }
+  }
+
+  if let Some(review_html) = &options.review_html {
+    if let Err(e) = check_review_path(Path::new(review_html.as_str()), input_path, Path::new(changed_options.output.as_str())) {
+      raise_error(e.as_str());
+    }
+  }
+
+  // queued jobs may be processed from another working directory
+  if let Mode::Queue(_) = options.mode {
+    if let Ok(input) = std::path::absolute(&changed_options.input) {
+      changed_options.input = input.to_string_lossy().to_string();
+    }
+    if let Ok(output) = std::path::absolute(&changed_options.output) {
+      changed_options.output = output.to_string_lossy().to_string();
+    }
+  }
+
   changed_options
 }
\ No newline at end of file
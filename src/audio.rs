@@ -0,0 +1,302 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Sample rate audio is decoded to when it has to go through ffmpeg.
+const DECODE_SAMPLE_RATE: u32 = 16000;
+
+/// Size of the largest fmt chunk, used by WAVE_FORMAT_EXTENSIBLE.
+const MAX_FMT_SIZE: u32 = 40;
+
+/// Decoded audio, mixed down to mono with samples between -1 and 1.
+pub struct AudioBuffer {
+  pub sample_rate: u32,
+  pub samples: Vec<f32>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum SampleFormat {
+  Int,
+  Float,
+}
+
+struct WavFormat {
+  format: SampleFormat,
+  channels: u16,
+  sample_rate: u32,
+  bits: u16,
+}
+
+impl AudioBuffer {
+  pub fn duration(&self) -> f64 {
+    self.samples.len() as f64 / self.sample_rate as f64
+  }
+
+  /// Returns the loudness in dBFS of consecutive windows of `window` seconds.
+  /// Digital silence is reported as -120 dB.
+  pub fn loudness(&self, window: f64) -> Vec<f64> {
+    let window = ((window * self.sample_rate as f64) as usize).max(1);
+    self.samples.chunks(window).map(|chunk| {
+      let sum: f64 = chunk.iter().map(|&s| (s as f64).powi(2)).sum();
+      let rms = (sum / chunk.len() as f64).sqrt();
+      if rms > 0.0 { (20.0 * rms.log10()).max(-120.0) } else { -120.0 }
+    }).collect()
+  }
+}
+
+fn read_exact_or_eof<R: Read>(reader: &mut R, buffer: &mut [u8]) -> std::io::Result<usize> {
+  let mut read = 0;
+  while read < buffer.len() {
+    match reader.read(&mut buffer[read..])? {
+      0 => break,
+      n => read += n,
+    }
+  }
+  Ok(read)
+}
+
+fn decode_sample(bytes: &[u8], format: SampleFormat) -> f32 {
+  match (format, bytes.len()) {
+    (SampleFormat::Int, 1) => (bytes[0] as f32 - 128.0) / 128.0,
+    (SampleFormat::Int, 2) => i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32768.0,
+    (SampleFormat::Int, 3) => (i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8) as f32 / 8388608.0,
+    (SampleFormat::Int, 4) => i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32 / 2147483648.0,
+    (SampleFormat::Float, 4) => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+    (SampleFormat::Float, 8) => f64::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]]) as f32,
+    _ => 0.0,
+  }
+}
+
+/// Reads interleaved frames until the reader is exhausted or `limit` bytes were read, mixing them down to mono.
+fn read_frames<R: Read>(reader: &mut R, format: &WavFormat, limit: Option<u64>) -> Result<Vec<f32>, String> {
+  if format.sample_rate == 0 {
+    return Err("Audio has a sample rate of 0.".to_string());
+  }
+  let sample_size = (format.bits as usize).div_ceil(8);
+  let frame_size = sample_size * format.channels as usize;
+  if frame_size == 0 {
+    return Err("Audio has no channels.".to_string());
+  }
+
+  let mut samples = Vec::new();
+  let mut buffer = vec![0; frame_size * 4096];
+  let mut remaining = limit.unwrap_or(u64::MAX);
+  while remaining > 0 {
+    let wanted = buffer.len().min(remaining.min(usize::MAX as u64) as usize);
+    let read = read_exact_or_eof(reader, &mut buffer[..wanted]).map_err(|e| e.to_string())?;
+    if read == 0 {
+      break;
+    }
+    remaining -= read as u64;
+    for frame in buffer[..read].chunks_exact(frame_size) {
+      let sum: f32 = frame.chunks_exact(sample_size).map(|sample| decode_sample(sample, format.format)).sum();
+      samples.push(sum / format.channels as f32);
+    }
+  }
+  Ok(samples)
+}
+
+/// Reads a RIFF/WAVE stream with integer (8 to 32 bit) or float (32 or 64 bit) samples.
+pub fn read_wav<R: Read>(mut reader: R) -> Result<AudioBuffer, String> {
+  let mut header = [0; 12];
+  if read_exact_or_eof(&mut reader, &mut header).map_err(|e| e.to_string())? < 12 || &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+    return Err("Not a WAV file.".to_string());
+  }
+
+  let mut format: Option<WavFormat> = None;
+  loop {
+    let mut chunk_header = [0; 8];
+    if read_exact_or_eof(&mut reader, &mut chunk_header).map_err(|e| e.to_string())? < 8 {
+      return Err("WAV file has no data chunk.".to_string());
+    }
+    let size = u32::from_le_bytes([chunk_header[4], chunk_header[5], chunk_header[6], chunk_header[7]]);
+
+    match &chunk_header[0..4] {
+      b"fmt " => {
+        if size > MAX_FMT_SIZE {
+          return Err("WAV format chunk is too long.".to_string());
+        }
+        let mut fmt = vec![0; size as usize + size as usize % 2];
+        reader.read_exact(&mut fmt).map_err(|e| e.to_string())?;
+        if fmt.len() < 16 {
+          return Err("WAV format chunk is too short.".to_string());
+        }
+        let mut tag = u16::from_le_bytes([fmt[0], fmt[1]]);
+        // WAVE_FORMAT_EXTENSIBLE stores the actual format in the sub format GUID
+        if tag == 0xFFFE && fmt.len() >= 26 {
+          tag = u16::from_le_bytes([fmt[24], fmt[25]]);
+        }
+        format = Some(WavFormat {
+          format: match tag {
+            1 => SampleFormat::Int,
+            3 => SampleFormat::Float,
+            _ => return Err(format!("Unsupported WAV sample format {}.", tag)),
+          },
+          channels: u16::from_le_bytes([fmt[2], fmt[3]]),
+          sample_rate: u32::from_le_bytes([fmt[4], fmt[5], fmt[6], fmt[7]]),
+          bits: u16::from_le_bytes([fmt[14], fmt[15]]),
+        });
+      },
+      b"data" => {
+        let format = format.ok_or("WAV data chunk precedes the format chunk.")?;
+        // streamed WAV files (e.g. from a pipe) do not know their size in advance
+        let limit = if size == 0 || size == u32::MAX { None } else { Some(size as u64) };
+        let samples = read_frames(&mut reader, &format, limit)?;
+        return Ok(AudioBuffer {
+          sample_rate: format.sample_rate,
+          samples,
+        });
+      },
+      _ => {
+        let skip = size as u64 + size as u64 % 2;
+        std::io::copy(&mut (&mut reader).take(skip), &mut std::io::sink()).map_err(|e| e.to_string())?;
+      },
+    }
+  }
+}
+
+/// Reads headerless signed 16 bit little endian PCM.
+pub fn read_raw_pcm<R: Read>(mut reader: R, sample_rate: u32, channels: u16) -> Result<AudioBuffer, String> {
+  let format = WavFormat {
+    format: SampleFormat::Int,
+    channels,
+    sample_rate,
+    bits: 16,
+  };
+  let samples = read_frames(&mut reader, &format, None)?;
+  Ok(AudioBuffer { sample_rate, samples })
+}
+
+/// Reads audio piped into stdin, either as WAV or as raw signed 16 bit little endian PCM.
+pub fn read_stdin(sample_rate: u32, channels: u16) -> Result<AudioBuffer, String> {
+  let mut bytes = Vec::new();
+  std::io::stdin().lock().read_to_end(&mut bytes).map_err(|e| e.to_string())?;
+  if bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WAVE" {
+    read_wav(bytes.as_slice())
+  } else {
+    read_raw_pcm(bytes.as_slice(), sample_rate, channels)
+  }
+}
+
+fn is_wav(path: &Path) -> bool {
+  let mut header = [0; 12];
+  match File::open(path) {
+    Ok(mut file) => matches!(read_exact_or_eof(&mut file, &mut header), Ok(12)) && &header[0..4] == b"RIFF" && &header[8..12] == b"WAVE",
+    Err(_) => false,
+  }
+}
+
+/// Loads the audio of a file. WAV files are read directly, everything else is decoded with ffmpeg.
+pub fn decode(path: &str) -> Result<AudioBuffer, String> {
+  if is_wav(Path::new(path)) {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    return read_wav(BufReader::new(file));
+  }
+
+  let mut child = Command::new("ffmpeg")
+    .args(["-v", "error", "-i", path, "-vn", "-ac", "1", "-ar", DECODE_SAMPLE_RATE.to_string().as_str(), "-f", "s16le", "-"])
+    .stdin(Stdio::null())
+    .stdout(Stdio::piped())
+    .spawn()
+    .map_err(|e| format!("Could not run ffmpeg to decode the audio: {}", e))?;
+
+  let buffer = match child.stdout.take() {
+    Some(stdout) => read_raw_pcm(BufReader::new(stdout), DECODE_SAMPLE_RATE, 1)?,
+    None => return Err("Could not read the decoded audio.".to_string()),
+  };
+  match child.wait() {
+    Ok(status) if status.success() => Ok(buffer),
+    _ => Err("ffmpeg failed to decode the audio of the input file.".to_string()),
+  }
+}
+
+/// Writes the buffer as a mono 16 bit WAV file.
+pub fn write_wav(path: &Path, buffer: &AudioBuffer) -> std::io::Result<()> {
+  let mut writer = BufWriter::new(File::create(path)?);
+  let data_size = buffer.samples.len() as u32 * 2;
+  writer.write_all(b"RIFF")?;
+  writer.write_all(&(36 + data_size).to_le_bytes())?;
+  writer.write_all(b"WAVEfmt ")?;
+  writer.write_all(&16u32.to_le_bytes())?;
+  writer.write_all(&1u16.to_le_bytes())?;
+  writer.write_all(&1u16.to_le_bytes())?;
+  writer.write_all(&buffer.sample_rate.to_le_bytes())?;
+  writer.write_all(&(buffer.sample_rate * 2).to_le_bytes())?;
+  writer.write_all(&2u16.to_le_bytes())?;
+  writer.write_all(&16u16.to_le_bytes())?;
+  writer.write_all(b"data")?;
+  writer.write_all(&data_size.to_le_bytes())?;
+  for sample in &buffer.samples {
+    writer.write_all(&((sample.clamp(-1.0, 1.0) * 32767.0) as i16).to_le_bytes())?;
+  }
+  writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn wav(fmt: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut bytes = b"RIFF\0\0\0\0WAVEfmt ".to_vec();
+    bytes.extend_from_slice(&(fmt.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(fmt);
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(data);
+    bytes
+  }
+
+  fn pcm_fmt(channels: u16, sample_rate: u32) -> Vec<u8> {
+    let mut fmt = Vec::new();
+    fmt.extend_from_slice(&1u16.to_le_bytes());
+    fmt.extend_from_slice(&channels.to_le_bytes());
+    fmt.extend_from_slice(&sample_rate.to_le_bytes());
+    fmt.extend_from_slice(&(sample_rate * 2 * channels as u32).to_le_bytes());
+    fmt.extend_from_slice(&(2 * channels).to_le_bytes());
+    fmt.extend_from_slice(&16u16.to_le_bytes());
+    fmt
+  }
+
+  #[test]
+  fn valid_wav() {
+    let data: Vec<u8> = [16384i16, -16384, 0, 32767].iter().flat_map(|sample| sample.to_le_bytes()).collect();
+    let buffer = read_wav(wav(&pcm_fmt(2, 8000), &data).as_slice()).unwrap();
+    assert_eq!(buffer.sample_rate, 8000);
+    assert_eq!(buffer.samples.len(), 2);
+    assert_eq!(buffer.samples[0], 0.0);
+    assert!((buffer.samples[1] - 0.5).abs() < 0.001);
+    assert_eq!(buffer.duration(), 2.0 / 8000.0);
+  }
+
+  #[test]
+  fn truncated_wav() {
+    let bytes = wav(&pcm_fmt(1, 8000), &[0; 8]);
+    assert!(read_wav(&bytes[..10]).is_err());
+    assert!(read_wav(&bytes[..30]).is_err());
+    // a missing part of the data is treated like the end of a stream
+    assert_eq!(read_wav(&bytes[..bytes.len() - 4]).unwrap().samples.len(), 2);
+  }
+
+  #[test]
+  fn malformed_fmt_chunk() {
+    assert!(read_wav(wav(&pcm_fmt(1, 8000)[..12], &[]).as_slice()).is_err());
+    assert!(read_wav(wav(&pcm_fmt(0, 8000), &[0; 4]).as_slice()).is_err());
+    assert!(read_wav(wav(&pcm_fmt(1, 0), &[0; 4]).as_slice()).is_err());
+
+    let mut adpcm = pcm_fmt(1, 8000);
+    adpcm[0] = 2;
+    assert!(read_wav(wav(&adpcm, &[0; 4]).as_slice()).is_err());
+
+    // the size field of the header must not decide how much memory is allocated
+    let mut huge = wav(&pcm_fmt(1, 8000), &[]);
+    huge[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(read_wav(huge.as_slice()).is_err());
+  }
+
+  #[test]
+  fn raw_pcm_needs_a_sample_rate() {
+    assert!(read_raw_pcm(&[0u8; 4][..], 0, 1).is_err());
+    assert_eq!(read_raw_pcm(&[0u8; 4][..], 16000, 2).unwrap().samples.len(), 1);
+  }
+}
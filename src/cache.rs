@@ -0,0 +1,127 @@
+extern crate dirs;
+extern crate sha2;
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use self::sha2::{Digest, Sha256};
+
+use crate::module_manager::{ArgumentResult, Cut, Generation, GeneratorStats};
+
+fn cache_dir() -> Option<PathBuf> {
+  // the cache is per user, as installed executables usually can not write next to themselves
+  Some(dirs::cache_dir()?.join("lecturecut"))
+}
+
+/// Computes the cache key for a generator run.
+/// The key covers the content of the input file, the generator version and the generator arguments.
+pub fn cache_key(input: &str, generator_version: &str, args: &[ArgumentResult]) -> Option<String> {
+  let mut hasher = Sha256::new();
+
+  let mut file = File::open(input).ok()?;
+  let mut buffer = vec![0; 1024 * 1024];
+  loop {
+    let read = file.read(&mut buffer).ok()?;
+    if read == 0 {
+      break;
+    }
+    hasher.update(&buffer[..read]);
+  }
+
+  hasher.update(b"\0");
+  hasher.update(generator_version.as_bytes());
+  for arg in args {
+    hasher.update(b"\0");
+    hasher.update(arg.long.as_bytes());
+    hasher.update(b"=");
+    hasher.update(arg.value.as_bytes());
+  }
+
+  Some(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Parses a stored generator result.
+/// The first line holds the stats, every following line one cut (same format as `--tsonly`).
+/// Every line ends with a newline, so a file that is cut short is not mistaken for a complete one.
+fn parse(content: &str) -> Option<Generation> {
+  if !content.ends_with('\n') {
+    return None;
+  }
+  let parse_pair = |line: &str| -> Option<(f64, f64)> {
+    let (a, b) = line.split_once(',')?;
+    Some((a.trim().parse().ok()?, b.trim().parse().ok()?))
+  };
+
+  let mut lines = content.lines();
+  let (len_pre_cut, len_post_cut) = parse_pair(lines.next()?)?;
+  let mut cuts: Vec<Cut> = Vec::new();
+  for line in lines {
+    let (start, end) = parse_pair(line)?;
+    cuts.push(Cut { start, end });
+  }
+
+  Some(Generation {
+    cuts,
+    stats: GeneratorStats { len_pre_cut, len_post_cut },
+  })
+}
+
+/// Loads a previously stored generator result. Unreadable or damaged entries count as a miss.
+pub fn load(key: &str) -> Option<Generation> {
+  let content = std::fs::read_to_string(cache_dir()?.join(format!("{}.csv", key))).ok()?;
+  parse(content.as_str())
+}
+
+pub fn store(key: &str, result: &Generation) {
+  let dir = if let Some(dir) = cache_dir() {
+    dir
+  } else {
+    return;
+  };
+  if let Err(e) = std::fs::create_dir_all(&dir) {
+    eprintln!("Error: Could not create cache directory: {}", e);
+    return;
+  }
+
+  let mut content = format!("{},{}\n", result.stats.len_pre_cut, result.stats.len_post_cut);
+  for cut in &result.cuts {
+    content += &format!("{},{}\n", cut.start, cut.end);
+  }
+
+  // other processes may read the entry at any time, so it only appears once it is complete
+  let path = dir.join(format!("{}.csv", key));
+  let tmp_path = dir.join(format!("{}.csv.{}.tmp", key, std::process::id()));
+  let result = File::create(&tmp_path)
+    .and_then(|mut file| {
+      file.write_all(content.as_bytes())?;
+      file.flush()
+    })
+    .and_then(|_| std::fs::rename(&tmp_path, &path));
+  if let Err(e) = result {
+    let _ = std::fs::remove_file(&tmp_path);
+    eprintln!("Error: Could not write cache file: {}", e);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn complete_entries() {
+    let gen = parse("30,14.5\n1,4\n6,9.5\n12,20\n").unwrap();
+    assert_eq!((gen.stats.len_pre_cut, gen.stats.len_post_cut), (30.0, 14.5));
+    assert_eq!(gen.cuts.iter().map(|cut| (cut.start, cut.end)).collect::<Vec<_>>(), [(1.0, 4.0), (6.0, 9.5), (12.0, 20.0)]);
+    assert!(parse("30,0\n").unwrap().cuts.is_empty());
+  }
+
+  #[test]
+  fn damaged_entries_are_a_miss() {
+    // written halfway, the last cut would silently end at 2 seconds instead of 20
+    assert!(parse("30,14.5\n1,4\n6,9.5\n12,2").is_none());
+    assert!(parse("30,14.5\n1,4\n6,").is_none());
+    assert!(parse("30,14.5\n1;4\n").is_none());
+    assert!(parse("").is_none());
+  }
+}
@@ -0,0 +1,200 @@
+use crate::audio::{self, AudioBuffer};
+use crate::module_manager::{Argument, ArgumentList, ArgumentResult, Callbacks, Cut, Generation, GeneratorStats, Generator, Module};
+use crate::printer::raise_error;
+
+// length of the analysis windows in seconds
+const WINDOW: f64 = 0.02;
+
+/// Built-in generator keeping every part of the input that is louder than a threshold.
+///
+/// Once the loudness exceeded `energy-threshold`, a segment is kept until the loudness stayed below
+/// `energy-threshold - energy-hysteresis` for longer than `energy-hold` seconds.
+/// It needs no compiled module and serves as a reference for module authors.
+pub struct EnergyGenerator;
+
+struct EnergySettings {
+  threshold: f64,
+  hysteresis: f64,
+  hold: f64,
+}
+
+impl EnergySettings {
+  fn from_args(args: &[ArgumentResult]) -> EnergySettings {
+    let mut settings = EnergySettings {
+      threshold: -40.0,
+      hysteresis: 6.0,
+      hold: 0.5,
+    };
+    for arg in args {
+      let value = match arg.value.parse::<f64>() {
+        Ok(value) => value,
+        Err(_) => {
+          raise_error(format!("--{} needs to be a number.", arg.long).as_str());
+          continue;
+        }
+      };
+      match arg.long.as_str() {
+        "energy-threshold" => settings.threshold = value,
+        "energy-hysteresis" => settings.hysteresis = value,
+        "energy-hold" => settings.hold = value,
+        _ => {},
+      }
+    }
+    settings
+  }
+}
+
+impl Module for EnergyGenerator {
+  fn version(&self) -> String {
+    format!("{} {}", self.name(), env!("CARGO_PKG_VERSION"))
+  }
+
+  fn arguments(&self) -> ArgumentList<'static> {
+    let arg = |long: &str, description: &str| Argument {
+      short: '\0',
+      long: long.to_string(),
+      description: description.to_string(),
+      required: false,
+      is_flag: false,
+    };
+    vec![
+      arg("energy-threshold", "Loudness in dBFS above which audio counts as speech. (default: -40)"),
+      arg("energy-hysteresis", "How many dB the loudness has to drop below the threshold to end a segment. (default: 6)"),
+      arg("energy-hold", "Seconds a segment is held open after the loudness dropped. (default: 0.5)"),
+    ]
+  }
+}
+
+impl Generator for EnergyGenerator {
+  fn name(&self) -> String {
+    "builtin-energy".to_string()
+  }
+
+  fn generate(&self, input: &str, args: &[ArgumentResult], callbacks: &Callbacks) -> Generation {
+    let settings = EnergySettings::from_args(args);
+
+    (callbacks.progress)("decoding", 0.0);
+    let buffer = match audio::decode(input) {
+      Ok(buffer) => buffer,
+      Err(e) => {
+        raise_error(e.as_str());
+        return Generation {
+          cuts: Vec::new(),
+          stats: GeneratorStats { len_pre_cut: 0.0, len_post_cut: 0.0 },
+        };
+      }
+    };
+    (callbacks.progress)("decoding", 1.0);
+
+    let cuts = detect_speech(&buffer, &settings, callbacks);
+    let len_post_cut = cuts.iter().map(|cut| cut.end - cut.start).sum();
+    Generation {
+      cuts,
+      stats: GeneratorStats {
+        len_pre_cut: buffer.duration(),
+        len_post_cut,
+      },
+    }
+  }
+}
+
+/// Finds the parts of `buffer` that are loud enough to keep.
+fn detect_speech(buffer: &AudioBuffer, settings: &EnergySettings, callbacks: &Callbacks) -> Vec<Cut> {
+  let duration = buffer.duration();
+  let windows = buffer.loudness(WINDOW);
+
+  let mut cuts = Vec::new();
+  // start of the current segment and end of the last window loud enough to keep it open
+  let mut segment: Option<(f64, f64)> = None;
+
+  for (i, &loudness) in windows.iter().enumerate() {
+    if i % 500 == 0 {
+      if (callbacks.cancelled)() {
+        break;
+      }
+      (callbacks.progress)("analysing", i as f64 / windows.len() as f64);
+    }
+
+    let start = i as f64 * WINDOW;
+    let end = (start + WINDOW).min(duration);
+
+    segment = match segment {
+      None if loudness >= settings.threshold => Some((start, end)),
+      None => None,
+      Some((segment_start, _)) if loudness >= settings.threshold - settings.hysteresis => Some((segment_start, end)),
+      Some((segment_start, last_loud)) if start - last_loud >= settings.hold => {
+        cuts.push(Cut { start: segment_start, end: (last_loud + settings.hold).min(duration) });
+        None
+      },
+      Some(segment) => Some(segment),
+    };
+  }
+  if let Some((segment_start, last_loud)) = segment {
+    cuts.push(Cut { start: segment_start, end: (last_loud + settings.hold).min(duration) });
+  }
+  (callbacks.progress)("analysing", 1.0);
+  cuts
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const LOUD: f32 = 0.1; // -20 dBFS
+  const MURMUR: f32 = 0.007; // about -43 dBFS, between the falling and the rising threshold
+  const QUIET: f32 = 0.001; // -60 dBFS
+
+  /// Builds a buffer of constant levels, each held for the given number of seconds.
+  fn buffer(parts: &[(f64, f32)]) -> AudioBuffer {
+    let sample_rate = 1000;
+    let mut samples = Vec::new();
+    for &(seconds, level) in parts {
+      samples.extend(std::iter::repeat_n(level, (seconds * sample_rate as f64).round() as usize));
+    }
+    AudioBuffer { sample_rate, samples }
+  }
+
+  fn detect(parts: &[(f64, f32)], args: &[(&str, &str)]) -> Vec<(f64, f64)> {
+    let args: Vec<ArgumentResult> = args.iter().map(|(long, value)| ArgumentResult { long: long.to_string(), value: value.to_string() }).collect();
+    let callbacks = Callbacks { progress: &|_, _| {}, cancelled: &|| false };
+    detect_speech(&buffer(parts), &EnergySettings::from_args(&args), &callbacks).iter()
+      // the windows are 20 ms long, so rounding to milliseconds removes floating point noise
+      .map(|cut| ((cut.start * 1000.0).round() / 1000.0, (cut.end * 1000.0).round() / 1000.0))
+      .collect()
+  }
+
+  #[test]
+  fn silence_is_removed() {
+    assert!(detect(&[(3.0, QUIET)], &[]).is_empty());
+    assert!(detect(&[(3.0, 0.0)], &[]).is_empty());
+  }
+
+  #[test]
+  fn segment_starts_at_the_rising_threshold() {
+    // murmur alone never opens a segment, but keeps an open one going
+    assert!(detect(&[(1.0, QUIET), (1.0, MURMUR), (1.0, QUIET)], &[]).is_empty());
+    assert_eq!(detect(&[(1.0, MURMUR), (1.0, LOUD), (1.0, MURMUR), (2.0, QUIET)], &[]), [(1.0, 3.5)]);
+  }
+
+  #[test]
+  fn segment_ends_below_the_falling_threshold() {
+    assert_eq!(detect(&[(1.0, QUIET), (1.0, LOUD), (2.0, QUIET)], &[]), [(1.0, 2.5)]);
+    // without hysteresis the murmur ends the segment as well
+    assert_eq!(detect(&[(1.0, LOUD), (1.0, MURMUR), (1.0, QUIET)], &[("energy-hysteresis", "0")]), [(0.0, 1.5)]);
+    // a lower threshold turns the murmur into speech
+    assert_eq!(detect(&[(1.0, QUIET), (1.0, MURMUR), (2.0, QUIET)], &[("energy-threshold", "-50")]), [(1.0, 2.5)]);
+  }
+
+  #[test]
+  fn short_pauses_are_held_open() {
+    assert_eq!(detect(&[(1.0, LOUD), (0.3, QUIET), (0.7, LOUD), (2.0, QUIET)], &[]), [(0.0, 2.5)]);
+    assert_eq!(detect(&[(1.0, LOUD), (1.0, QUIET), (1.0, LOUD), (1.0, QUIET)], &[]), [(0.0, 1.5), (2.0, 3.5)]);
+    assert_eq!(detect(&[(1.0, LOUD), (1.0, QUIET), (1.0, LOUD), (2.0, QUIET)], &[("energy-hold", "1.2")]), [(0.0, 4.2)]);
+  }
+
+  #[test]
+  fn hold_ends_with_the_input() {
+    assert_eq!(detect(&[(1.0, QUIET), (1.0, LOUD)], &[]), [(1.0, 2.0)]);
+    assert_eq!(detect(&[(1.0, LOUD), (0.2, QUIET)], &[]), [(0.0, 1.2)]);
+  }
+}
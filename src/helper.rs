@@ -1,32 +1,333 @@
-pub fn get_automatic_path(file: &str, tsonly: bool) -> String {
-  let automatic_name_insert = "_lecturecut";
+use std::path::{Component, Path};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-  // if windows replace \ with / for now
-  let file = file.replace('\\', "/");
+use crate::argmunents::Options;
+use crate::printer::raise_error;
 
-  // split file in path and extension
-  let file_stuff = file.split('/').collect::<Vec<&str>>();
-  let file_path = file_stuff[0..file_stuff.len() - 1].join("/");
-  let file_name = file_stuff[file_stuff.len() - 1];
-  let file_name_stuff = file_name.split('.').collect::<Vec<&str>>();
-  let file_name_without_extension = file_name_stuff[0..file_name_stuff.len() - 1].join(".");
-  let file_extension = if tsonly {"csv"} else {file_name_stuff[file_name_stuff.len() - 1]};
+/// The template used for automatically generated output names.
+pub const DEFAULT_NAME_TEMPLATE: &str = "{stem}_{suffix}.{ext}";
 
-  let new_file_name = format!("{}{}.{}", file_name_without_extension, automatic_name_insert, file_extension);
+const NAME_SUFFIX: &str = "lecturecut";
 
-  let file = if !file_path.is_empty() {
-    format!("{}/{}", file_path, new_file_name)
-  } else {
-    new_file_name
+/// Containers `--audio-only` can produce.
+pub const AUDIO_FORMATS: [&str; 3] = ["m4a", "opus", "mp3"];
+
+pub fn is_audio_file(file: &str) -> bool {
+  Path::new(file).extension().is_some_and(|ext| AUDIO_FORMATS.iter().any(|format| ext == *format))
+}
+
+/// Replaces every `{placeholder}` in `template` with its value.
+/// `{{` and `}}` produce literal braces.
+fn fill_template(template: &str, values: &[(&str, &str)]) -> Result<String, String> {
+  let mut result = String::new();
+  let mut chars = template.chars();
+  while let Some(c) = chars.next() {
+    match c {
+      '{' => {
+        let mut name = String::new();
+        loop {
+          match chars.next() {
+            Some('{') if name.is_empty() => {
+              result.push('{');
+              break;
+            },
+            Some('}') => {
+              match values.iter().find(|(placeholder, _)| *placeholder == name) {
+                Some((_, value)) => result += value,
+                None => return Err(format!("Unknown placeholder {{{}}} in \"{}\".", name, template)),
+              }
+              break;
+            },
+            Some(c) => name.push(c),
+            None => return Err(format!("Unclosed placeholder in \"{}\".", template)),
+          }
+        }
+      },
+      '}' => {
+        if chars.next() != Some('}') {
+          return Err(format!("Unmatched }} in \"{}\".", template));
+        }
+        result.push('}');
+      },
+      c => result.push(c),
+    }
+  }
+  Ok(result)
+}
+
+/// Converts days since the unix epoch into a (year, month, day) date.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+  // see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+  let z = days + 719468;
+  let era = z.div_euclid(146097);
+  let doe = z.rem_euclid(146097);
+  let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = doy - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+  (year, month as u32, day as u32)
+}
+
+/// Returns the current (UTC) date as YYYY-MM-DD.
+pub fn today() -> String {
+  let days = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() / 86400).unwrap_or(0);
+  let (year, month, day) = civil_from_days(days as i64);
+  format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Everything the automatic output path depends on besides the input file.
+struct Naming<'a> {
+  /// Template for the file name, placed next to the input.
+  template: &'a str,
+  /// Template for the whole path, overriding `template`.
+  output_template: Option<&'a str>,
+  tsonly: bool,
+  /// Container overriding the extension of the input.
+  format: Option<&'a str>,
+  generator: &'a str,
+  preset: &'a str,
+  date: &'a str,
+}
+
+impl<'a> Naming<'a> {
+  fn from_options(options: &'a Options, date: &'a str) -> Naming<'a> {
+    Naming {
+      template: options.name_template.as_str(),
+      output_template: options.output_template.as_deref(),
+      tsonly: options.tsonly,
+      format: options.container(),
+      generator: options.generator_name.as_str(),
+      // render modules offering a preset argument name the quality / speed trade-off with it
+      preset: options.render_args.iter().find(|arg| arg.long == "preset").map(|arg| arg.value.as_str()).unwrap_or("default"),
+      date,
+    }
+  }
+}
+
+fn automatic_path(file: &str, dir: Option<&Path>, naming: &Naming) -> Result<String, String> {
+  let path = Path::new(file);
+  let stem = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+  let ext = match (naming.tsonly, naming.format) {
+    (true, _) => "csv".to_string(),
+    (false, Some(format)) => format.to_string(),
+    (false, None) => path.extension().map(|ext| ext.to_string_lossy().to_string()).unwrap_or_default(),
+  };
+  let dir_value = match (dir, path.parent()) {
+    (Some(dir), _) => dir.to_string_lossy().to_string(),
+    (None, Some(parent)) if !parent.as_os_str().is_empty() => parent.to_string_lossy().to_string(),
+    _ => ".".to_string(),
   };
 
-  if cfg!(windows) {
-    file.replace('/', "\\")
+  let template = naming.output_template.unwrap_or(naming.template);
+  // files without an extension should not end up with a trailing dot
+  let template = if ext.is_empty() { template.replace(".{ext}", "{ext}") } else { template.to_string() };
+  let mut values = vec![
+    ("stem", stem.as_str()),
+    ("suffix", NAME_SUFFIX),
+    ("ext", ext.as_str()),
+    ("date", naming.date),
+    ("generator", naming.generator),
+    ("preset", naming.preset),
+  ];
+  if naming.output_template.is_some() {
+    values.push(("dir", dir_value.as_str()));
+  }
+  let name = fill_template(template.as_str(), &values)?;
+
+  Ok(match (naming.output_template, dir) {
+    (Some(_), _) => name,
+    (None, Some(dir)) => dir.join(name).to_string_lossy().to_string(),
+    (None, None) => path.with_file_name(name).to_string_lossy().to_string(),
+  })
+}
+
+/// Checks that `--name-template` and `--output-template` only use known placeholders.
+pub fn validate_templates(options: &Options) -> Result<(), String> {
+  let mut naming = Naming::from_options(options, "");
+  automatic_path("video.mp4", None, &naming).map_err(|e| format!("Invalid --name-template: {}", e))?;
+  if let Some(output_template) = options.output_template.as_deref() {
+    naming.output_template = Some(output_template);
+    automatic_path("video.mp4", None, &naming).map_err(|e| format!("Invalid --output-template: {}", e))?;
+  }
+  Ok(())
+}
+
+/// Generates the output path for `file` from the name templates of `options`.
+/// The file is placed in `dir`, or next to the input if there is none.
+/// Templates may use `{stem}`, `{suffix}`, `{ext}`, `{date}`, `{generator}` and `{preset}`,
+/// the output template additionally `{dir}`.
+pub fn get_automatic_path(file: &str, dir: Option<&Path>, options: &Options) -> String {
+  let date = today();
+  // the templates are validated on startup, so this does not fail for user input
+  automatic_path(file, dir, &Naming::from_options(options, date.as_str())).unwrap_or_else(|e| {
+    raise_error(e.as_str());
+    String::new()
+  })
+}
+
+/// Generates the output path for `file` in the output directory `dir` of a batch.
+/// Without an output template the file keeps its name and only gets the extension of the output container,
+/// or .csv with `--tsonly`.
+pub fn get_path_in_dir(file: &Path, dir: &Path, options: &Options) -> String {
+  if options.output_template.is_some() {
+    return get_automatic_path(file.to_string_lossy().as_ref(), Some(dir), options);
+  }
+  let mut output_path = dir.join(file.file_name().unwrap_or(file.as_os_str()));
+  if options.tsonly {
+    output_path.set_extension("csv");
+  } else if let Some(container) = options.container() {
+    output_path.set_extension(container);
+  }
+  output_path.to_string_lossy().to_string()
+}
+
+/// The folder of a relative `file`, or an empty path if the file lies outside of the working directory.
+pub fn relative_parent(file: &Path) -> &Path {
+  match file.parent() {
+    Some(parent) if parent.components().all(|component| matches!(component, Component::Normal(_) | Component::CurDir)) => parent,
+    _ => Path::new(""),
+  }
+}
+
+pub fn read_input_list(input_list: &str) -> std::io::Result<String> {
+  if input_list == "-" {
+    let mut content = String::new();
+    std::io::Read::read_to_string(&mut std::io::stdin().lock(), &mut content)?;
+    Ok(content)
   } else {
-    file
+    std::fs::read_to_string(input_list)
   }
 }
 
+/// Parses a list of inputs, one per line and optionally followed by a tab and the output.
+/// Empty lines and lines starting with `#` are skipped.
+pub fn parse_input_list(content: &str) -> Vec<(String, Option<String>)> {
+  content.lines()
+    .map(str::trim)
+    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+    .map(|line| match line.split_once('\t') {
+      Some((input, output)) if !output.trim().is_empty() => (input.trim_end().to_string(), Some(output.trim_start().to_string())),
+      Some((input, _)) => (input.trim_end().to_string(), None),
+      None => (line.to_string(), None),
+    })
+    .collect()
+}
+
+pub fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+  // renaming fails across file systems (e.g. from a network share), so fall back to copying
+  if std::fs::rename(from, to).is_err() {
+    std::fs::copy(from, to)?;
+    std::fs::remove_file(from)?;
+  }
+  Ok(())
+}
+
 pub fn make_clickable_link(text: &str, link: &str) -> String {
   format!("\u{1b}]8;;{}\u{1b}\\{}\u{1b}]8;;\u{1b}\\", link, text)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn naming(template: &str) -> Naming<'_> {
+    Naming {
+      template,
+      output_template: None,
+      tsonly: false,
+      format: None,
+      generator: "builtin-energy",
+      preset: "default",
+      date: "2024-03-01",
+    }
+  }
+
+  fn path(file: &str, tsonly: bool, template: &str) -> String {
+    automatic_path(file, None, &Naming { tsonly, ..naming(template) }).unwrap()
+  }
+
+  #[test]
+  fn default_template() {
+    assert_eq!(path("lecture.mp4", false, DEFAULT_NAME_TEMPLATE), "lecture_lecturecut.mp4");
+    assert_eq!(path("videos/lecture.mp4", false, DEFAULT_NAME_TEMPLATE), "videos/lecture_lecturecut.mp4");
+    assert_eq!(path("/videos/lecture.mp4", true, DEFAULT_NAME_TEMPLATE), "/videos/lecture_lecturecut.csv");
+  }
+
+  #[test]
+  fn multiple_dots() {
+    assert_eq!(path("week.1.lecture.mkv", false, DEFAULT_NAME_TEMPLATE), "week.1.lecture_lecturecut.mkv");
+    assert_eq!(path("my.videos/lecture", false, DEFAULT_NAME_TEMPLATE), "my.videos/lecture_lecturecut");
+  }
+
+  #[test]
+  fn no_extension() {
+    assert_eq!(path("lecture", false, DEFAULT_NAME_TEMPLATE), "lecture_lecturecut");
+    assert_eq!(path("lecture", true, DEFAULT_NAME_TEMPLATE), "lecture_lecturecut.csv");
+  }
+
+  #[test]
+  fn dotfiles() {
+    assert_eq!(path(".lecture", false, DEFAULT_NAME_TEMPLATE), ".lecture_lecturecut");
+    assert_eq!(path("videos/.lecture.mp4", false, DEFAULT_NAME_TEMPLATE), "videos/.lecture_lecturecut.mp4");
+  }
+
+  #[test]
+  fn custom_template() {
+    assert_eq!(path("lecture.mp4", false, "{date}-{stem}.{generator}.{ext}"), "2024-03-01-lecture.builtin-energy.mp4");
+    assert_eq!(path("videos/lecture.mp4", false, "{{{stem}}}.{ext}"), "videos/{lecture}.mp4");
+    assert!(automatic_path("lecture.mp4", None, &naming("{dir}/{stem}.{ext}")).is_err());
+  }
+
+  #[test]
+  fn output_template() {
+    let naming = Naming { output_template: Some("{dir}/{stem}.cut.{preset}.{ext}"), ..naming(DEFAULT_NAME_TEMPLATE) };
+    assert_eq!(automatic_path("videos/lecture.mp4", None, &naming).unwrap(), "videos/lecture.cut.default.mp4");
+    assert_eq!(automatic_path("lecture.mp4", None, &naming).unwrap(), "./lecture.cut.default.mp4");
+    assert_eq!(automatic_path("videos/lecture.mp4", Some(Path::new("out")), &naming).unwrap(), "out/lecture.cut.default.mp4");
+  }
+
+  #[test]
+  fn format_overrides_extension() {
+    let naming = Naming { format: Some("mkv"), ..naming(DEFAULT_NAME_TEMPLATE) };
+    assert_eq!(automatic_path("videos/lecture.mp4", None, &naming).unwrap(), "videos/lecture_lecturecut.mkv");
+    assert_eq!(automatic_path("lecture", Some(Path::new("out")), &naming).unwrap(), "out/lecture_lecturecut.mkv");
+    let naming = Naming { tsonly: true, ..naming };
+    assert_eq!(automatic_path("lecture.mp4", None, &naming).unwrap(), "lecture_lecturecut.csv");
+  }
+
+  #[test]
+  fn invalid_templates() {
+    assert!(automatic_path("lecture.mp4", None, &naming("{stem}_{name}.{ext}")).is_err());
+    assert!(automatic_path("lecture.mp4", None, &naming("{stem")).is_err());
+    assert!(automatic_path("lecture.mp4", None, &naming("stem}")).is_err());
+  }
+
+  #[test]
+  fn input_lists() {
+    let list = parse_input_list("# lectures\nweek 1/monday.mp4\n\n  week 2/tuesday.mp4\tcut/tuesday.mp4\nfriday.mp4\t\n");
+    assert_eq!(list, vec![
+      ("week 1/monday.mp4".to_string(), None),
+      ("week 2/tuesday.mp4".to_string(), Some("cut/tuesday.mp4".to_string())),
+      ("friday.mp4".to_string(), None),
+    ]);
+  }
+
+  #[test]
+  fn relative_parents() {
+    assert_eq!(relative_parent(Path::new("week 1/monday.mp4")), Path::new("week 1"));
+    assert_eq!(relative_parent(Path::new("./week 1/monday.mp4")), Path::new("./week 1"));
+    assert_eq!(relative_parent(Path::new("monday.mp4")), Path::new(""));
+    assert_eq!(relative_parent(Path::new("/videos/monday.mp4")), Path::new(""));
+    assert_eq!(relative_parent(Path::new("../videos/monday.mp4")), Path::new(""));
+  }
+
+  #[test]
+  fn dates() {
+    assert_eq!(civil_from_days(0), (1970, 1, 1));
+    assert_eq!(civil_from_days(19782), (2024, 2, 29));
+    assert_eq!(civil_from_days(-1), (1969, 12, 31));
+    assert_eq!(today().len(), 10);
+  }
+}
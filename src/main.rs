@@ -1,12 +1,24 @@
-extern crate libloading;
 extern crate console;
 extern crate indicatif;
 extern crate clap;
+extern crate ctrlc;
 
 mod argmunents;
 mod printer;
 mod helper;
 mod module_manager;
+mod cache;
+mod watch;
+mod server;
+mod queue;
+mod energy_generator;
+mod audio;
+mod timeline;
+mod subtitles;
+mod review;
+mod tui;
+mod manifest;
+mod probe;
 
 extern crate once_cell;
 use once_cell::sync::Lazy;
@@ -14,115 +26,312 @@ use once_cell::sync::Lazy;
 use std::fs::File;
 use std::io::Write;
 use std::collections::HashMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
 
-use argmunents::{parse_args, validate_args, Options};
+use argmunents::{check_listed_output, check_render_support, parse_args, parse_offline_args, selected_generator, validate_args, Mode, Options, QueueAction};
 use indicatif::ProgressBar;
 use indicatif::ProgressStyle;
-use module_manager::GeneratorStats;
-use printer::print_stats;
+use module_manager::{ArgumentList, ArgumentResult, Callbacks, Cut, Generation, GeneratorStats, Generator, Renderer, CAPABILITY_AUDIO_ONLY};
+use printer::{print_cancelled, print_cancelling};
+use printer::{print_diff, print_evaluation, print_mapped_time, print_queue, print_serving, print_stats, print_sweep, print_timeline, print_watching};
 use self::indicatif::MultiProgress;
-use self::console::style;
-use crate::module_manager::generator_generate;
-use crate::module_manager::render_render;
+use self::console::{style, Term};
 
-use self::libloading::Library;
-use module_manager::{load_render, load_generator, module_version};
-use printer::{greetings, print_non_mp4_warning};
-
-use std::ffi::c_char;
-use std::ffi::c_double;
+use module_manager::{load_render, load_generator};
+use printer::{catch_errors, greetings, print_input_warning, raise_error};
+use probe::MediaInfo;
 
 struct ProgressWrapper {
   pub progress: Option<MultiProgress>,
   pub pbars: Lazy<HashMap<String, ProgressBar>>,
+  pub values: Lazy<HashMap<String, f64>>,
 }
 
 static PROG_WRAPPER: Mutex<ProgressWrapper> = Mutex::new(ProgressWrapper {
   progress: Option::None,
   pbars: Lazy::new(HashMap::new),
+  values: Lazy::new(HashMap::new),
 });
 
 
 
-fn run(options: &Options, generator: &Library, render: &Library) -> GeneratorStats  {
+static CANCEL_REQUESTED: AtomicBool = AtomicBool::new(false);
+static WORKING: AtomicBool = AtomicBool::new(false);
+static PARTIAL_OUTPUT: Mutex<String> = Mutex::new(String::new());
+// temporary WAV file holding the audio read from stdin
+static STDIN_AUDIO: Mutex<String> = Mutex::new(String::new());
+
+fn cancel_requested() -> bool {
+  CANCEL_REQUESTED.load(Ordering::SeqCst)
+}
+
+fn remove_partial_output() {
+  if let Ok(mut partial_output) = PARTIAL_OUTPUT.lock() {
+    if !partial_output.is_empty() && Path::new(partial_output.as_str()).exists() {
+      if let Err(e) = std::fs::remove_file(partial_output.as_str()) {
+        eprintln!("Error: Could not remove {}: {}", partial_output, e);
+      }
+    }
+    partial_output.clear();
+  }
+}
+
+fn remove_stdin_audio() {
+  if let Ok(mut stdin_audio) = STDIN_AUDIO.lock() {
+    if !stdin_audio.is_empty() && Path::new(stdin_audio.as_str()).exists() {
+      if let Err(e) = std::fs::remove_file(stdin_audio.as_str()) {
+        eprintln!("Error: Could not remove {}: {}", stdin_audio, e);
+      }
+    }
+    stdin_audio.clear();
+  }
+}
+
+fn exit_if_cancelled() {
+  if !CANCEL_REQUESTED.load(Ordering::SeqCst) {
+    return;
+  }
+  remove_partial_output();
+  remove_stdin_audio();
   if let Ok(locked_prog) = PROG_WRAPPER.lock() {
     if let Some(prog) = locked_prog.progress.as_ref() {
-      if let Err(e) = prog.println(format!(" Input: {}", style(&options.input).yellow())) {
+      if let Err(e) = prog.clear() {
         eprintln!("Error: {}", e);
       }
-      if let Err(e) = prog.println(format!("Output: {}", style(&options.output).yellow())) {
-        eprintln!("Error: {}", e);
+    }
+  }
+  print_cancelled();
+  std::process::exit(130);
+}
+
+fn generate(options: &Options, info: &MediaInfo, generator: &dyn Generator, callbacks: &Callbacks) -> Generation {
+  let key = if options.no_cache {
+    None
+  } else {
+    cache::cache_key(options.input.as_str(), generator.version().as_str(), &options.generator_args)
+  };
+
+  let mut gen = if let Some(gen) = key.as_ref().and_then(|key| cache::load(key)) {
+    if let Ok(locked_prog) = PROG_WRAPPER.lock() {
+      if let Some(prog) = locked_prog.progress.as_ref() {
+        if let Err(e) = prog.println(format!("  Cuts: {}", style("loaded from cache").green())) {
+          eprintln!("Error: {}", e);
+        }
       }
-      if let Err(e) = prog.println("") {
-        eprintln!("Error: {}", e);
+    }
+    gen
+  } else {
+    let gen = generator.generate(options.input.as_str(), &options.generator_args, callbacks);
+    // results of a cancelled generator are incomplete and must not end up in the cache
+    exit_if_cancelled();
+    // a dry run does not write any files, not even the cache
+    if let (Some(key), false) = (key, options.dry_run) {
+      cache::store(key.as_str(), &gen);
+    }
+    gen
+  };
+
+  // not every generator knows the length of its input
+  if gen.stats.len_pre_cut <= 0.0 {
+    if let Some(duration) = info.duration {
+      gen.stats.len_pre_cut = duration;
+      gen.stats.len_post_cut = timeline::kept_length(&gen.cuts);
+    }
+  }
+
+  // trimming happens after caching, as it does not depend on the generator
+  if options.trim_start > 0.0 || options.trim_end > 0.0 {
+    if gen.stats.len_pre_cut <= 0.0 {
+      raise_error(format!("The duration of {} is unknown, so it cannot be trimmed.", options.input).as_str());
+    }
+    let cuts = timeline::trim(&gen.cuts, options.trim_start, gen.stats.len_pre_cut - options.trim_end);
+    return Generation {
+      stats: GeneratorStats {
+        len_pre_cut: gen.stats.len_pre_cut,
+        len_post_cut: timeline::kept_length(&cuts),
+      },
+      cuts,
+    };
+  }
+  gen
+}
+
+fn report_progress(name: &str, value: f64) {
+  // lock progress.mutex
+  if let Ok(mut locked_prog) = PROG_WRAPPER.lock() {
+    locked_prog.values.insert(name.to_string(), value);
+
+    // find progress bar
+    if let Some(pb) = locked_prog.pbars.get(name) {
+      pb.set_position((value * 1000.) as u64);
+    }
+    // if not found, create new progress bar
+    else if let Some(prog) = locked_prog.progress.as_ref() {
+      let pb = prog.add(ProgressBar::new(1000));
+      if let Ok(style) = ProgressStyle::with_template("{spinner:.green} {msg} {bar:40.green/magenta} {percent:>3} % • {elapsed_precise:.yellow} • {eta_precise:.cyan}") {
+        pb.set_style(
+          style
+          .progress_chars("━╸━")
+        );
       }
+      pb.set_position((value * 1000.0) as u64);
+      let padded_name = format!("{: >16}", name);
+      pb.set_message(padded_name);
+      locked_prog.pbars.insert(name.to_string(), pb);
     }
     
     drop(locked_prog);
   }
+}
 
-  if tree_magic::from_filepath(Path::new(options.input.as_str())) != "video/mp4" {
-    print_non_mp4_warning();
+/// Writes the files accompanying the output, e.g. chapters.
+fn write_sidecars(options: &Options, gen: &Generation) {
+  if let Some(min_gap) = options.chapters {
+    let chapters = timeline::chapters(&gen.cuts, min_gap);
+    let output = Path::new(options.output.as_str());
+    let result = match options.chapter_format.as_str() {
+      "ffmetadata" => timeline::write_ffmetadata_chapters(&output.with_extension("ffmetadata"), &chapters),
+      _ => timeline::write_webvtt_chapters(&output.with_extension("chapters.vtt"), &chapters),
+    };
+    if let Err(e) = result {
+      eprintln!("Error: Could not write chapters: {}", e);
+    }
+  }
+  if let Some(subtitles) = &options.subtitles {
+    if let Err(e) = subtitles::retime_file(Path::new(subtitles), Path::new(options.output.as_str()), &gen.cuts) {
+      eprintln!("Error: Could not retime subtitles: {}", e);
+    }
+  }
+  if let Some(review_html) = &options.review_html {
+    if let Err(e) = review::write_review_html(Path::new(review_html), options.input.as_str(), options.output.as_str(), gen) {
+      eprintln!("Error: Could not write review page: {}", e);
+    }
+  }
+  if options.timestamp_map {
+    if let Err(e) = timeline::write_map(&Path::new(options.output.as_str()).with_extension("map.csv"), &gen.cuts) {
+      eprintln!("Error: Could not write timestamp map: {}", e);
+    }
   }
+}
 
-  unsafe extern fn progress_callback(name: *const c_char, value: c_double) {
-    // decode name
-    if let Ok(name) = std::ffi::CStr::from_ptr(name).to_str() {
-    // lock progress.mutex
-      if let Ok(mut locked_prog) = PROG_WRAPPER.lock() {
+/// Runs the generator and compares its cuts with hand-labelled speech regions.
+fn evaluate_generator(options: &Options, truth: &str, generator: &dyn Generator) {
+  let truth = match timeline::read_cuts(Path::new(truth)) {
+    Ok(truth) => truth,
+    Err(e) => {
+      raise_error(format!("Could not read the labels: {}", e).as_str());
+      return;
+    }
+  };
+  let callbacks = Callbacks {
+    progress: &report_progress,
+    cancelled: &cancel_requested,
+  };
 
-        // find progress bar
-        if let Some(pb) = locked_prog.pbars.get(name) {
-          pb.set_position((value * 1000.) as u64);
-        }
-        // if not found, create new progress bar
-        else if let Some(prog) = locked_prog.progress.as_ref() {
-          let pb = prog.add(ProgressBar::new(1000));
-          if let Ok(style) = ProgressStyle::with_template("{spinner:.green} {msg} {bar:40.green/magenta} {percent:>3} % • {elapsed_precise:.yellow} • {eta_precise:.cyan}") {
-            pb.set_style(
-              style
-              .progress_chars("━╸━")
-            );
-          }
-          pb.set_position((value * 1000.0) as u64);
-          let padded_name = format!("{: >16}", name);
-          pb.set_message(padded_name);
-          locked_prog.pbars.insert(name.to_string(), pb);
-        }
-        
-        drop(locked_prog);
+  WORKING.store(true, Ordering::SeqCst);
+  let gen = generate(options, &probe::probe(Path::new(options.input.as_str())), generator, &callbacks);
+  clear_progress_bars();
+  WORKING.store(false, Ordering::SeqCst);
+
+  print_evaluation(&timeline::evaluate(&gen.cuts, &truth));
+}
+
+/// Runs the generator for every combination of the swept argument values.
+fn sweep_generator(options: &Options, sweeps: &[(String, Vec<String>)], generator: &dyn Generator) {
+  let callbacks = Callbacks {
+    progress: &report_progress,
+    cancelled: &cancel_requested,
+  };
+  let info = probe::probe(Path::new(options.input.as_str()));
+  let flags: Vec<String> = generator.arguments().into_iter().filter(|arg| arg.is_flag).map(|arg| arg.long).collect();
+
+  // every combination of values, the first argument changing slowest
+  let mut combinations: Vec<Vec<String>> = vec![Vec::new()];
+  for (_, values) in sweeps {
+    combinations = combinations.into_iter().flat_map(|combination| values.iter().map(move |value| {
+      let mut combination = combination.clone();
+      combination.push(value.clone());
+      combination
+    })).collect();
+  }
+
+  let mut rows: Vec<(Vec<String>, Generation)> = Vec::new();
+  for values in combinations {
+    let mut generator_args: Vec<ArgumentResult> = options.generator_args.iter()
+      .filter(|arg| !sweeps.iter().any(|(name, _)| *name == arg.long))
+      .cloned()
+      .collect();
+    for ((name, _), value) in sweeps.iter().zip(&values) {
+      // flags are swept with true and false, where false means not passing them
+      if flags.contains(name) && value == "false" {
+        continue;
       }
+      generator_args.push(ArgumentResult { long: name.clone(), value: value.clone() });
     }
+
+    WORKING.store(true, Ordering::SeqCst);
+    let gen = generate(&Options { generator_args, ..options.clone() }, &info, generator, &callbacks);
+    clear_progress_bars();
+    WORKING.store(false, Ordering::SeqCst);
+    rows.push((values, gen));
   }
 
-  if options.tsonly {
-    let gen = generator_generate(generator, options.input.as_str(), options.generator_args.clone().into(), progress_callback);
-    let file = File::create(options.output.as_str());
-    if let Ok(mut file) = file {
-      for i in 0..gen.cuts.length {
-        unsafe {
-          if let Some(cut) = gen.cuts.cuts.offset(i as isize).as_ref() {
-            if let Err(e) = file.write_all(format!("{},{}\n", cut.start, cut.end).as_bytes()) {
-              eprintln!("Error: {}", e);
-            }
-          }
-        }
-      }
-      if let Err(e) = file.flush() {
+  let names: Vec<String> = sweeps.iter().map(|(name, _)| name.clone()).collect();
+  print_sweep(&names, &rows);
+}
+
+fn diff_cut_lists(before: &str, after: &str) {
+  let (before, after) = match (timeline::read_cuts(Path::new(before)), timeline::read_cuts(Path::new(after))) {
+    (Ok(before), Ok(after)) => (before, after),
+    (Err(e), _) | (_, Err(e)) => {
+      raise_error(format!("Could not read the cut list: {}", e).as_str());
+      return;
+    }
+  };
+  let union = timeline::kept_length(&before) + timeline::kept_length(&after) - timeline::intersection_length(&before, &after);
+  let overlap = if union > 0.0 { timeline::intersection_length(&before, &after) / union } else { 1.0 };
+  print_diff(&timeline::diff_cuts(&before, &after), timeline::kept_length(&before), timeline::kept_length(&after), overlap);
+}
+
+fn map_timestamp(map: &str, time: f64, to_input: bool) {
+  let cuts = match timeline::read_cuts(Path::new(map)) {
+    Ok(cuts) => cuts,
+    Err(e) => {
+      raise_error(format!("Could not read the timestamp map: {}", e).as_str());
+      return;
+    }
+  };
+  if to_input {
+    match timeline::output_to_input(&cuts, time) {
+      Some(input_time) => print_mapped_time("Output", time, "Input", input_time, true),
+      None => raise_error("The timestamp lies beyond the end of the output."),
+    }
+  } else {
+    let (output_time, kept) = timeline::input_to_output(&cuts, time);
+    print_mapped_time("Input", time, "Output", output_time, kept);
+  }
+}
+
+/// Writes the cuts as CSV, as done by `--tsonly`.
+fn write_cuts(output: &str, cuts: &[Cut]) {
+  let file = File::create(output);
+  if let Ok(mut file) = file {
+    for cut in cuts {
+      if let Err(e) = file.write_all(format!("{},{}\n", cut.start, cut.end).as_bytes()) {
         eprintln!("Error: {}", e);
       }
-    } else {
-      panic!("Failed to create output file");
     }
-    return gen.stats;
+    if let Err(e) = file.flush() {
+      eprintln!("Error: {}", e);
+    }
+  } else {
+    panic!("Failed to create output file");
   }
+}
 
-  let gen = generator_generate(generator, options.input.as_str(), options.generator_args.clone().into(), progress_callback);
-  
-  render_render(render, options.input.as_str(), options.output.as_str(), gen.cuts, options.render_args.clone().into(), progress_callback);
-
+fn clear_progress_bars() {
   if let Ok(mut locked_prog) = PROG_WRAPPER.lock() {
     for (_, pb) in locked_prog.pbars.iter() {
       pb.finish_and_clear();
@@ -133,58 +342,420 @@ fn run(options: &Options, generator: &Library, render: &Library) -> GeneratorSta
     locked_prog.pbars.clear();
     drop(locked_prog);
   }
-  gen.stats
 }
 
-fn process_files_in_dir(options: Options, generator: Library, render: Library) {
+/// Renders the cuts into the output and writes the sidecar files.
+fn render_cuts(options: &Options, render: &dyn Renderer, gen: &Generation, callbacks: &Callbacks) {
+  if let Ok(mut partial_output) = PARTIAL_OUTPUT.lock() {
+    *partial_output = options.output.clone();
+  }
+  render.render(options.input.as_str(), options.output.as_str(), &gen.cuts, &options.render_args, callbacks);
+  exit_if_cancelled();
+  if let Ok(mut partial_output) = PARTIAL_OUTPUT.lock() {
+    partial_output.clear();
+  }
+
+  clear_progress_bars();
+  write_sidecars(options, gen);
+}
+
+fn run(options: &Options, info: &MediaInfo, generator: &dyn Generator, render: &dyn Renderer) -> Generation  {
+  WORKING.store(true, Ordering::SeqCst);
+  if let Ok(mut locked_prog) = PROG_WRAPPER.lock() {
+    locked_prog.values.clear();
+
+    if let Some(prog) = locked_prog.progress.as_ref() {
+      if let Err(e) = prog.println(format!(" Input: {}", style(&options.input).yellow())) {
+        eprintln!("Error: {}", e);
+      }
+      if let Err(e) = prog.println(format!("Output: {}", style(&options.output).yellow())) {
+        eprintln!("Error: {}", e);
+      }
+      if let Err(e) = prog.println("") {
+        eprintln!("Error: {}", e);
+      }
+    }
+    
+    drop(locked_prog);
+  }
+
+  for warning in info.warnings() {
+    print_input_warning(warning.as_str());
+  }
+
+  // output templates may point into directories that do not exist yet
+  if let (Some(parent), false) = (Path::new(options.output.as_str()).parent(), options.dry_run) {
+    if let Err(e) = std::fs::create_dir_all(parent) {
+      eprintln!("Error: {}", e);
+    }
+  }
+
+  let callbacks = Callbacks {
+    progress: &report_progress,
+    cancelled: &cancel_requested,
+  };
+
+  if options.tsonly && !options.dry_run {
+    let gen = generate(options, info, generator, &callbacks);
+    write_cuts(options.output.as_str(), &gen.cuts);
+    write_sidecars(options, &gen);
+    WORKING.store(false, Ordering::SeqCst);
+    return gen;
+  }
+
+  let gen = generate(options, info, generator, &callbacks);
+
+  if options.dry_run {
+    WORKING.store(false, Ordering::SeqCst);
+    return gen;
+  }
+  
+  render_cuts(options, render, &gen, &callbacks);
+  WORKING.store(false, Ordering::SeqCst);
+  gen
+}
+
+/// Processes a single file for one of the long running modes.
+/// Errors are returned instead of exiting, so the remaining files are still processed.
+fn run_recoverable(options: &Options, info: &MediaInfo, generator: &dyn Generator, render: &dyn Renderer) -> Result<Generation, String> {
+  let result = catch_errors(|| run(options, info, generator, render));
+  if result.is_err() {
+    remove_partial_output();
+    clear_progress_bars();
+    WORKING.store(false, Ordering::SeqCst);
+  }
+  result
+}
+
+/// Shows the statistics of all processed files, followed by the timeline of every file.
+/// Nothing was written in a dry run, so there are only the timelines.
+fn print_results(results: Vec<(String, String, Generation)>, time_used: std::time::Duration, dry_run: bool) {
+  if !dry_run {
+    print_stats(results.iter().map(|(input, output, gen)| (input.clone(), output.clone(), gen.stats.clone())).collect(), time_used);
+  }
+  for (input, _, gen) in &results {
+    if results.len() > 1 {
+      println!(" Input: {}", style(input).yellow());
+    }
+    print_timeline(&gen.cuts, gen.stats.len_pre_cut);
+  }
+}
+
+fn process_files_in_dir(options: Options, generator: &dyn Generator, render: &dyn Renderer) {
+  let mut manifest = match &options.manifest {
+    Some(path) => manifest::load(Path::new(path), &generator.arguments(), &render.arguments()).unwrap_or_else(|e| {
+      raise_error(format!("Invalid manifest: {}", e).as_str());
+      HashMap::new()
+    }),
+    None => HashMap::new(),
+  };
+  let output_dir = Path::new(if options.output.is_empty() { &options.input } else { &options.output });
+
+  let mut jobs = Vec::new();
+  let mut outputs = Vec::new();
   if let Ok(files) = Path::new(&options.input).read_dir() {
     // map files to paths
     let files: Vec<_> = files.filter_map(|f| if let Ok(f) = f {Some(f.path())} else {None}).collect();
     let files: Vec<_> = files.into_iter().filter(|f| f.is_file()).collect();
-    let files: Vec<_> = files.into_iter().filter(|f| tree_magic::from_filepath(f).starts_with("video")).collect();
+    let files: Vec<_> = files.into_iter().map(|f| (probe::probe(&f), f)).filter(|(info, _)| info.is_video()).collect();
 
-    for file in files {
+    for (info, file) in files {
       if let Some(file_path) = file.to_str() {
-        let output_path = if !options.output.is_empty() {
-          let tmp = if let Some(tmp) = Path::new(&options.output).join(if let Some(filename) = file.file_name() {
-            filename
-          } else {
-            panic!("Failed to get filename");
-          }
-        ).to_str() {
-            tmp
-          } else {
-            "\\"
-          }.to_string();
-          tmp
+        let output_path = if options.output.is_empty() {
+          helper::get_automatic_path(file_path, None, &options)
         } else {
-          let tmp = helper::get_automatic_path(if let Some(filename) = file.to_str() {
-            filename
-          } else {
-            panic!("Failed to get filename");
-          }, options.tsonly);
-          tmp
+          helper::get_path_in_dir(&file, Path::new(&options.output), &options)
         };
 
-        let options = Options {
+        let mut options = Options {
           input: file_path.to_string(),
           output: output_path.to_string(),
           ..options.clone()
         };
+        let overrides = file.file_name().and_then(|name| manifest.remove(name.to_string_lossy().as_ref()));
+        if let Some(overrides) = &overrides {
+          overrides.apply(&mut options, output_dir);
+        }
+        check_listed_output(&options, file_path, options.output.as_str(), overrides.is_some_and(|overrides| overrides.output.is_some()), &outputs);
+        outputs.push((options.input.clone(), options.output.clone()));
+        jobs.push((options, info));
+      }
+    }
+  }
+
+  // incompatible files are reported before the first one is processed
+  if !options.tsonly && !options.dry_run {
+    for (options, info) in &jobs {
+      if let Err(e) = check_render_support(render, Path::new(options.input.as_str()), info, Path::new(options.output.as_str())) {
+        raise_error(e.as_str());
+      }
+    }
+  }
+
+  let start = std::time::Instant::now();
+  let mut results = Vec::new();
+  for (options, info) in jobs {
+    let gen = run(&options, &info, generator, render);
+    results.push((options.input, options.output, gen));
+  }
+
+  if !results.is_empty() {
+    print_results(results, start.elapsed(), options.dry_run);
+  }
+
+  for name in manifest.keys() {
+    eprintln!("Warning: {} is listed in the manifest but was not processed.", name);
+  }
+}
+
+/// Processes every file of an input list and shows the statistics of all of them at the end.
+fn process_input_list(options: Options, generator: &dyn Generator, render: &dyn Renderer) {
+  let jobs: Vec<_> = options.inputs.iter().map(|(input, output)| (probe::probe(Path::new(input.as_str())), input, output)).collect();
+
+  // incompatible files are reported before the first one is processed
+  if !options.tsonly && !options.dry_run {
+    for (info, input, output) in &jobs {
+      if let Err(e) = check_render_support(render, Path::new(input.as_str()), info, Path::new(output.as_str())) {
+        raise_error(e.as_str());
+      }
+    }
+  }
+
+  let start = std::time::Instant::now();
+  let mut results = Vec::new();
+  for (info, input, output) in jobs {
+    let options = Options {
+      input: input.clone(),
+      output: output.clone(),
+      ..options.clone()
+    };
+    let gen = run(&options, &info, generator, render);
+    results.push((options.input, options.output, gen));
+  }
+
+  if !results.is_empty() {
+    print_results(results, start.elapsed(), options.dry_run);
+  }
+}
+
+fn process_watched_dir(options: Options, archive: &str, settle: u64, generator: &dyn Generator, render: &dyn Renderer) {
+  print_watching(options.input.as_str(), options.output.as_str(), archive);
+
+  watch::watch_dir(Path::new(&options.input), std::time::Duration::from_secs(settle), |file| {
+    let info = probe::probe(file);
+    if !info.is_video() {
+      return;
+    }
+    let (file_path, file_name) = match (file.to_str(), file.file_name().and_then(|name| name.to_str())) {
+      (Some(file_path), Some(file_name)) => (file_path, file_name),
+      _ => return,
+    };
+
+    let output_path = PathBuf::from(helper::get_automatic_path(file_path, Some(Path::new(&options.output)), &options));
+    if output_path.exists() {
+      eprintln!("Warning: {} already exists, skipping {}.", output_path.display(), file_name);
+      return;
+    }
+
+    let options = Options {
+      input: file_path.to_string(),
+      output: output_path.to_string_lossy().to_string(),
+      ..options.clone()
+    };
+    if !options.tsonly {
+      if let Err(e) = check_render_support(render, file, &info, &output_path) {
+        eprintln!("Error: Could not process {}: {}", file_name, e);
+        return;
+      }
+    }
+
+    let start = std::time::Instant::now();
+    let gen = match run_recoverable(&options, &info, generator, render) {
+      Ok(gen) => gen,
+      Err(e) => {
+        // the file stays in the watched folder, so it can be fixed and dropped in again
+        eprintln!("Error: Could not process {}: {}", file_name, e);
+        return;
+      }
+    };
+    let end = std::time::Instant::now();
+
+    // the stats read the size of the input, so they are printed before it is archived
+    print_results(vec![(options.input, options.output, gen)], end - start, options.dry_run);
+
+    if let Err(e) = helper::move_file(file, &Path::new(archive).join(file_name)) {
+      eprintln!("Error: Could not archive {}: {}", file_name, e);
+    }
+  });
+}
+
+fn current_progress() -> HashMap<String, f64> {
+  if let Ok(locked_prog) = PROG_WRAPPER.lock() {
+    locked_prog.values.clone()
+  } else {
+    HashMap::new()
+  }
+}
+
+fn serve_jobs(options: Options, bind: &str, generator_args: ArgumentList, render_args: ArgumentList, generator: &dyn Generator, render: &dyn Renderer) {
+  print_serving(bind);
+
+  // jobs are probed when they are submitted, so incompatible ones are rejected right away
+  let prepare = |options: &mut Options| {
+    let info = probe::probe(Path::new(options.input.as_str()));
+    if !options.tsonly {
+      check_render_support(render, Path::new(options.input.as_str()), &info, Path::new(options.output.as_str()))?;
+    }
+    options.info = Some(info);
+    Ok(())
+  };
+  server::serve(bind, &options, &generator_args, &render_args, prepare, |options| {
+    let info = options.info.clone().unwrap_or_else(|| probe::probe(Path::new(options.input.as_str())));
+    run_recoverable(options, &info, generator, render).map(|gen| (gen.cuts, gen.stats))
+  }, current_progress);
+}
+
+fn manage_queue(options: Options, action: QueueAction, generator: &dyn Generator, render: &dyn Renderer) {
+  match action {
+    QueueAction::Add { priority } => {
+      let id = queue::add(queue::QueuedJob {
+        id: 0,
+        priority,
+        status: queue::JobStatus::Queued,
+        input: options.input,
+        output: options.output,
+        tsonly: options.tsonly,
+        generator_args: options.generator_args,
+        render_args: options.render_args,
+        stats: None,
+        error: None,
+        worker: None,
+      });
+      println!("Added job {} to the queue.", style(id).cyan());
+    },
+    QueueAction::List => print_queue(&queue::load()),
+    QueueAction::Cancel { id } => queue::cancel(id),
+    QueueAction::Priority { id, priority } => queue::set_priority(id, priority),
+    QueueAction::Run => {
+      let _worker = queue::register_worker();
+      queue::requeue_interrupted();
+
+      let start = std::time::Instant::now();
+      let mut processed = Vec::new();
+      let mut failed = 0;
+      while let Some(job) = queue::start_next() {
+        let options = Options {
+          input: job.input,
+          output: job.output,
+          tsonly: job.tsonly,
+          generator_args: job.generator_args,
+          render_args: job.render_args,
+          ..options.clone()
+        };
+        // the render module may have changed since the job was added
+        let info = probe::probe(Path::new(options.input.as_str()));
+        let result = if options.tsonly {
+          Ok(())
+        } else {
+          check_render_support(render, Path::new(options.input.as_str()), &info, Path::new(options.output.as_str()))
+        };
+        match result.and_then(|_| run_recoverable(&options, &info, generator, render)) {
+          Ok(gen) => {
+            queue::finish(job.id, gen.stats.clone());
+            processed.push((options.input, options.output, gen));
+          },
+          Err(e) => {
+            eprintln!("Error: Job {} failed: {}", job.id, e);
+            queue::fail(job.id, e);
+            failed += 1;
+          },
+        }
+      }
+      let end = std::time::Instant::now();
 
-        run(&options, &generator, &render);
+      if processed.is_empty() && failed == 0 {
+        println!("The queue is empty.");
+      } else if !processed.is_empty() {
+        print_results(processed, end - start, options.dry_run);
       }
+    },
+  }
+}
+
+/// Stores audio piped into stdin in a temporary WAV file, so modules can read it like any other input.
+fn read_stdin_audio(options: &Options) -> Option<String> {
+  let buffer = match audio::read_stdin(options.pcm_rate, options.pcm_channels) {
+    Ok(buffer) => buffer,
+    Err(e) => {
+      raise_error(format!("Could not read audio from stdin: {}", e).as_str());
+      return None;
     }
+  };
+  let path = std::env::temp_dir().join(format!("lecturecut-stdin-{}.wav", std::process::id()));
+  if let Ok(mut stdin_audio) = STDIN_AUDIO.lock() {
+    *stdin_audio = path.to_string_lossy().to_string();
+  }
+  if let Err(e) = audio::write_wav(&path, &buffer) {
+    raise_error(format!("Could not write {}: {}", path.display(), e).as_str());
+    return None;
   }
+  Some(path.to_string_lossy().to_string())
 }
 
-fn process_single_file(options: Options, generator: Library, render: Library) {
+/// Generates the cuts, lets the user edit them in the terminal and renders the result.
+fn review_file(options: Options, generator: &dyn Generator, render: &dyn Renderer) {
+  if !Term::stdout().is_term() {
+    raise_error("The review needs an interactive terminal.");
+  }
+  let start = std::time::Instant::now();
+  let callbacks = Callbacks {
+    progress: &report_progress,
+    cancelled: &cancel_requested,
+  };
+
+  WORKING.store(true, Ordering::SeqCst);
+  let gen = generate(&options, &probe::probe(Path::new(options.input.as_str())), generator, &callbacks);
+  clear_progress_bars();
+  WORKING.store(false, Ordering::SeqCst);
+
+  let duration = gen.cuts.last().map(|cut| cut.end.max(gen.stats.len_pre_cut)).unwrap_or(gen.stats.len_pre_cut);
+  let cuts = match tui::review_cuts(options.input.as_str(), &gen.cuts, duration) {
+    Some(cuts) => cuts,
+    None => {
+      println!("Review aborted, nothing was written.");
+      return;
+    }
+  };
+  let gen = Generation {
+    stats: GeneratorStats {
+      len_pre_cut: gen.stats.len_pre_cut,
+      len_post_cut: cuts.iter().map(|cut| cut.end - cut.start).sum(),
+    },
+    cuts,
+  };
+
+  WORKING.store(true, Ordering::SeqCst);
+  if options.tsonly {
+    write_cuts(options.output.as_str(), &gen.cuts);
+    write_sidecars(&options, &gen);
+  } else {
+    render_cuts(&options, render, &gen, &callbacks);
+  }
+  WORKING.store(false, Ordering::SeqCst);
+
+  let end = std::time::Instant::now();
+  print_results(vec![(options.input, options.output, gen)], end - start, options.dry_run);
+}
+
+fn process_single_file(options: Options, generator: &dyn Generator, render: &dyn Renderer) {
   // start timer
   let start = std::time::Instant::now();
-  let stats = run(&options, &generator, &render);
+  // the input was probed while validating the arguments, except for audio read from stdin
+  let info = options.info.clone().unwrap_or_else(|| probe::probe(Path::new(options.input.as_str())));
+  let gen = run(&options, &info, generator, render);
   // stop timer
   let end = std::time::Instant::now();
-  print_stats([(options.input, options.output, stats)].to_vec(), end - start);
+  print_results(vec![(options.input, options.output, gen)], end - start, options.dry_run);
 }
 
 fn main() {
@@ -196,23 +767,84 @@ fn main() {
     drop(locked_prog);
   }
 
+  // the first Ctrl-C asks the modules to stop, a second one (or one while idle) exits immediately
+  if let Err(e) = ctrlc::set_handler(|| {
+    if !WORKING.load(Ordering::SeqCst) || CANCEL_REQUESTED.swap(true, Ordering::SeqCst) {
+      remove_partial_output();
+      remove_stdin_audio();
+      std::process::exit(130);
+    }
+    print_cancelling();
+  }) {
+    eprintln!("Error: {}", e);
+  }
+
+  // map and diff only read files written earlier, so they need no module and print nothing but their result
+  if let Some(options) = parse_offline_args() {
+    match options.mode {
+      Mode::Map { time, to_input } => map_timestamp(options.input.as_str(), time, to_input),
+      Mode::Diff { other } => diff_cut_lists(options.input.as_str(), other.as_str()),
+      _ => {},
+    }
+    return;
+  }
+
   let render = load_render();
-  let render_version = module_version(&render);
-  let generator = load_generator();
-  let generator_version = module_version(&generator);
+  let render_version = render.version();
+  let generator = load_generator(selected_generator().as_str());
+  let generator_version = generator.version();
 
   greetings(render_version.as_str(), generator_version.as_str());
 
-  let generator_args = module_manager::module_get_arguments(&generator);
-  let render_args = module_manager::module_get_arguments(&render);
+  let generator_args = generator.arguments();
+  let render_args = render.arguments();
+
+  let mut options = parse_args(generator.name().as_str(), generator_args.clone(), render_args.clone());
+  options = validate_args(options, render.as_ref());
 
-  let mut options = parse_args(generator_args, render_args);
-  options = validate_args(options);
+  if options.audio_only.is_some() && render.capabilities() & CAPABILITY_AUDIO_ONLY == 0 {
+    raise_error("The render module does not support --audio-only.");
+  }
 
-  if Path::new(options.input.as_str()).is_dir() {
-    process_files_in_dir(options, generator, render);
+  if let Mode::Serve { bind } = options.mode.clone() {
+    serve_jobs(options, bind.as_str(), generator_args, render_args, generator.as_ref(), render.as_ref());
+  }
+  else if let Mode::Queue(action) = options.mode.clone() {
+    manage_queue(options, action, generator.as_ref(), render.as_ref());
+  }
+  else if let Mode::Review = options.mode {
+    review_file(options, generator.as_ref(), render.as_ref());
+  }
+  else if let Mode::Eval { truth } = options.mode.clone() {
+    evaluate_generator(&options, truth.as_str(), generator.as_ref());
+  }
+  else if let Mode::Sweep { args } = options.mode.clone() {
+    sweep_generator(&options, &args, generator.as_ref());
+  }
+  else if let Mode::Watch { archive, settle } = options.mode.clone() {
+    process_watched_dir(options, archive.as_str(), settle, generator.as_ref(), render.as_ref());
+  }
+  else if options.input_list.is_some() {
+    process_input_list(options, generator.as_ref(), render.as_ref());
+  }
+  else if options.input == "-" {
+    // errors are caught, so the temporary file is removed before exiting
+    let result = catch_errors(|| {
+      if let Some(input) = read_stdin_audio(&options) {
+        process_single_file(Options { input, ..options }, generator.as_ref(), render.as_ref());
+      }
+    });
+    remove_stdin_audio();
+    if let Err(e) = result {
+      remove_partial_output();
+      clear_progress_bars();
+      raise_error(e.as_str());
+    }
+  }
+  else if Path::new(options.input.as_str()).is_dir() {
+    process_files_in_dir(options, generator.as_ref(), render.as_ref());
   }
   else {
-    process_single_file(options, generator, render);
+    process_single_file(options, generator.as_ref(), render.as_ref());
   }
 }
\ No newline at end of file
@@ -0,0 +1,218 @@
+extern crate serde;
+extern crate toml;
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Component, Path};
+
+use self::serde::Deserialize;
+
+use crate::argmunents::Options;
+use crate::module_manager::{ArgumentList, ArgumentResult};
+
+/// Overrides for a single file of a processed directory.
+#[derive(Default)]
+pub struct FileOverrides {
+  /// Name of the output file, placed in the output directory.
+  pub output: Option<String>,
+  pub trim_start: Option<f64>,
+  pub trim_end: Option<f64>,
+  pub generator_args: Vec<ArgumentResult>,
+  pub render_args: Vec<ArgumentResult>,
+  /// Flags switched off for this file.
+  pub cleared_flags: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct TomlManifest {
+  #[serde(default)]
+  file: Vec<TomlEntry>,
+}
+
+#[derive(Deserialize)]
+struct TomlEntry {
+  name: String,
+  output: Option<String>,
+  trim_start: Option<f64>,
+  trim_end: Option<f64>,
+  #[serde(default)]
+  args: BTreeMap<String, toml::Value>,
+}
+
+/// A manifest entry before its arguments are assigned to the generator or the render module.
+struct Entry {
+  name: String,
+  overrides: FileOverrides,
+  args: Vec<(String, String)>,
+}
+
+fn parse_seconds(value: &str, column: &str) -> Result<Option<f64>, String> {
+  if value.is_empty() {
+    return Ok(None);
+  }
+  value.parse().map(Some).map_err(|_| format!("{} needs to be a number of seconds, got {}.", column, value))
+}
+
+/// Reads a CSV manifest. The header names the columns: `file` first, then `output`, `trim_start`, `trim_end`
+/// and module arguments in any order. Empty cells keep the value given on the command line.
+/// Quoted values are not supported, so no value may contain a comma.
+fn parse_csv(content: &str) -> Result<Vec<Entry>, String> {
+  let mut lines = content.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+  let header: Vec<String> = match lines.next() {
+    Some((_, header)) => header.split(',').map(|column| column.trim().to_string()).collect(),
+    None => return Ok(Vec::new()),
+  };
+  if header.first().map(String::as_str) != Some("file") {
+    return Err("The first column of the manifest needs to be \"file\".".to_string());
+  }
+
+  let mut entries = Vec::new();
+  for (i, line) in lines {
+    if line.contains('"') {
+      return Err(format!("Line {} of the manifest contains quotes, which are not supported.", i + 1));
+    }
+    let values: Vec<&str> = line.split(',').map(str::trim).collect();
+    if values.len() != header.len() {
+      return Err(format!("Line {} of the manifest has {} columns, but the header has {}.", i + 1, values.len(), header.len()));
+    }
+    let mut entry = Entry {
+      name: String::new(),
+      overrides: FileOverrides::default(),
+      args: Vec::new(),
+    };
+    for (column, value) in header.iter().zip(values) {
+      match column.as_str() {
+        "file" => entry.name = value.to_string(),
+        _ if value.is_empty() => {},
+        "output" => entry.overrides.output = Some(value.to_string()),
+        "trim_start" => entry.overrides.trim_start = parse_seconds(value, column)?,
+        "trim_end" => entry.overrides.trim_end = parse_seconds(value, column)?,
+        _ => entry.args.push((column.clone(), value.to_string())),
+      }
+    }
+    entries.push(entry);
+  }
+  Ok(entries)
+}
+
+/// Reads a TOML manifest with one `[[file]]` table per file.
+fn parse_toml(content: &str) -> Result<Vec<Entry>, String> {
+  let manifest: TomlManifest = toml::from_str(content).map_err(|e| e.to_string())?;
+  Ok(manifest.file.into_iter().map(|file| Entry {
+    name: file.name,
+    overrides: FileOverrides {
+      output: file.output,
+      trim_start: file.trim_start,
+      trim_end: file.trim_end,
+      ..FileOverrides::default()
+    },
+    args: file.args.into_iter().map(|(name, value)| match value {
+      toml::Value::String(value) => (name, value),
+      value => (name, value.to_string()),
+    }).collect(),
+  }).collect())
+}
+
+/// Loads the per-file overrides of a manifest, keyed by file name.
+pub fn load(path: &Path, generator_args: &ArgumentList, render_args: &ArgumentList) -> Result<HashMap<String, FileOverrides>, String> {
+  let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+  let entries = match path.extension().and_then(|ext| ext.to_str()) {
+    Some("toml") => parse_toml(content.as_str())?,
+    _ => parse_csv(content.as_str())?,
+  };
+
+  let mut manifest = HashMap::new();
+  for mut entry in entries {
+    if entry.name.is_empty() {
+      return Err("Every manifest entry needs a file name.".to_string());
+    }
+    if manifest.contains_key(&entry.name) {
+      return Err(format!("{} is listed more than once.", entry.name));
+    }
+    // outputs are placed in the output directory and may not leave it
+    if let Some(output) = &entry.overrides.output {
+      if !Path::new(output).components().all(|component| matches!(component, Component::Normal(_) | Component::CurDir)) {
+        return Err(format!("The output {} of {} needs to be a path inside the output directory.", output, entry.name));
+      }
+    }
+    for (name, value) in entry.args {
+      let is_generator_arg = generator_args.iter().any(|arg| arg.long == name);
+      let arg = match generator_args.iter().chain(render_args.iter()).find(|arg| arg.long == name) {
+        Some(arg) => arg,
+        None => return Err(format!("Unknown argument {} for {}.", name, entry.name)),
+      };
+      if arg.is_flag && value == "false" {
+        entry.overrides.cleared_flags.push(name);
+        continue;
+      }
+      let result = ArgumentResult { long: name, value };
+      if is_generator_arg {
+        entry.overrides.generator_args.push(result);
+      } else {
+        entry.overrides.render_args.push(result);
+      }
+    }
+    manifest.insert(entry.name, entry.overrides);
+  }
+  Ok(manifest)
+}
+
+/// Replaces arguments given on the command line.
+fn merge_args(args: &mut Vec<ArgumentResult>, overrides: &[ArgumentResult], cleared_flags: &[String]) {
+  args.retain(|arg| !cleared_flags.contains(&arg.long) && !overrides.iter().any(|result| result.long == arg.long));
+  args.extend(overrides.iter().cloned());
+}
+
+impl FileOverrides {
+  pub fn apply(&self, options: &mut Options, output_dir: &Path) {
+    if let Some(output) = &self.output {
+      options.output = output_dir.join(output).to_string_lossy().to_string();
+    }
+    options.trim_start = self.trim_start.unwrap_or(options.trim_start);
+    options.trim_end = self.trim_end.unwrap_or(options.trim_end);
+    merge_args(&mut options.generator_args, &self.generator_args, &self.cleared_flags);
+    merge_args(&mut options.render_args, &self.render_args, &self.cleared_flags);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn csv_columns_by_header() {
+    let entries = parse_csv("file, trim_end ,output,mock-scale\n\nfirst.mp4,12,,2\nsecond.mp4,,cut.mp4,\n").unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].name, "first.mp4");
+    assert_eq!(entries[0].overrides.trim_end, Some(12.0));
+    assert_eq!(entries[0].overrides.output, None);
+    assert_eq!(entries[0].args, [("mock-scale".to_string(), "2".to_string())]);
+    assert_eq!(entries[1].overrides.output.as_deref(), Some("cut.mp4"));
+    assert!(entries[1].args.is_empty());
+  }
+
+  #[test]
+  fn csv_rejects_quotes_and_ragged_rows() {
+    let error = |content: &str| parse_csv(content).err().unwrap();
+    assert_eq!(error("file,output\n\"a,b.mp4\",cut.mp4\n"), "Line 2 of the manifest contains quotes, which are not supported.");
+    assert_eq!(error("file,output\n\nfirst.mp4\n"), "Line 3 of the manifest has 1 columns, but the header has 2.");
+    assert_eq!(error("file,output\nfirst.mp4,a,b\n"), "Line 2 of the manifest has 3 columns, but the header has 2.");
+    assert!(error("output,file\n").contains("first column"));
+    assert!(error("file,trim_start\nfirst.mp4,soon\n").contains("number of seconds"));
+  }
+
+  #[test]
+  fn outputs_stay_in_the_output_directory() {
+    let path = std::env::temp_dir().join(format!("lecturecut-test-{}-manifest.csv", std::process::id()));
+    let load_output = |output: &str| {
+      std::fs::write(&path, format!("file,output\nfirst.mp4,{}\n", output)).unwrap();
+      load(&path, &Vec::new(), &Vec::new()).map(|mut manifest| manifest.remove("first.mp4").unwrap().output.unwrap())
+    };
+    assert_eq!(load_output("cut.mp4"), Ok("cut.mp4".to_string()));
+    assert_eq!(load_output("week 1/cut.mp4"), Ok("week 1/cut.mp4".to_string()));
+    assert!(load_output("../../escaped.mp4").unwrap_err().contains("inside the output directory"));
+    assert!(load_output("week 1/../../escaped.mp4").is_err());
+    assert!(load_output("/tmp/escaped.mp4").is_err());
+    assert_eq!(load_output("./cut.mp4"), Ok("./cut.mp4".to_string()));
+    std::fs::remove_file(&path).unwrap();
+  }
+}
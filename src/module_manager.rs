@@ -1,4 +1,5 @@
 extern crate libloading;
+extern crate serde;
 
 use std::ffi::CStr;
 use std::ffi::CString;
@@ -7,27 +8,55 @@ use std::ffi::c_double;
 use std::ffi::c_long;
 use std::ffi::c_void;
 use std::path::Path;
+use std::sync::Mutex;
 
-use crate::printer::raise_error;
+use crate::argmunents::selected_generator;
+use crate::energy_generator::EnergyGenerator;
+use crate::printer::{errors_are_recoverable, print_builtin_generator_warning, raise_error};
 
 use self::libloading::Symbol;
+use self::serde::{Deserialize, Serialize};
 
 use self::libloading::Library;
 
 #[repr(C)]
+#[derive(Clone, Copy, Serialize)]
 pub struct Cut {
   pub start: c_double,
   pub end: c_double,
 }
 
 #[repr(C)]
+#[derive(Clone, Copy)]
 pub struct CutList {
   pub length: c_long,
   pub cuts: *const Cut,
 }
 
+impl CutList {
+  pub fn to_vec(self) -> Vec<Cut> {
+    let mut cuts: Vec<Cut> = Vec::new();
+    for i in 0..self.length {
+      if let Some(cut) = unsafe { self.cuts.offset(i as isize).as_ref() } {
+        cuts.push(*cut);
+      }
+    }
+    cuts
+  }
+}
+
+impl From<&[Cut]> for CutList {
+  // the list borrows the cuts, so it must not outlive them
+  fn from(cuts: &[Cut]) -> CutList {
+    CutList {
+      length: cuts.len() as c_long,
+      cuts: cuts.as_ptr(),
+    }
+  }
+}
+
 #[repr(C)]
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct GeneratorStats {
   pub len_pre_cut: c_double,
   pub len_post_cut: c_double,
@@ -49,6 +78,7 @@ pub struct CArgument {
   pub is_flag: bool,
 }
 
+#[derive(Clone)]
 pub struct Argument {
   pub short: char,
   pub long: String,
@@ -90,7 +120,7 @@ impl From<CArgumentList> for ArgumentList<'static> {
   }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct ArgumentResult {
   pub long: String,
   pub value: String,
@@ -131,20 +161,169 @@ impl From<Vec<ArgumentResult>> for CArgumentResultList {
   }
 }
 
-type ErrorCallback = unsafe extern fn(*const c_char) -> ();
-type ProgressCallback = unsafe extern fn(*const c_char, c_double) -> ();
+type ErrorCallback = unsafe extern "C" fn(*const c_char) -> ();
+type ProgressCallback = unsafe extern "C" fn(*const c_char, c_double) -> ();
+// Returns true once the user requested to cancel. Modules should poll it and return early.
+// It is passed as the last argument, so modules built before it existed simply ignore it.
+type CancelCallback = unsafe extern "C" fn() -> bool;
+
+type CapabilitiesFunc<'a> = Symbol<'a, unsafe extern "C" fn(ErrorCallback) -> u32>;
+type SupportsFunc<'a> = Symbol<'a, unsafe extern "C" fn(*const c_char, *const c_char, *const c_char, ErrorCallback) -> bool>;
+type NameFunc<'a> = Symbol<'a, unsafe extern "C" fn(ErrorCallback) -> *const c_char>;
+type InitFunc<'a> = Symbol<'a, unsafe extern "C" fn(ErrorCallback) -> ()>;
+type VersionFunc<'a> = Symbol<'a, unsafe extern "C" fn(ErrorCallback) -> *const c_char>;
+type GetArgumentsFunc<'a> = Symbol<'a, unsafe extern "C" fn(ErrorCallback) -> CArgumentList>;
+type RenderFunc<'a> = Symbol<'a, unsafe extern "C" fn(*const c_char, *const c_char, CutList, CArgumentResultList, ProgressCallback, ErrorCallback, CancelCallback) -> c_void>;
+type GenerateFunc<'a> = Symbol<'a, unsafe extern "C" fn(*const c_char, CArgumentResultList, ProgressCallback, ErrorCallback, CancelCallback) -> GeneratorResult>;
+
+/// Owned result of a generator run.
+#[derive(Clone)]
+pub struct Generation {
+  pub cuts: Vec<Cut>,
+  pub stats: GeneratorStats,
+}
+
+/// Callbacks the host hands to a module while it is working.
+pub struct Callbacks<'a> {
+  /// Reports the progress (0 to 1) of the named step.
+  pub progress: &'a (dyn Fn(&str, f64) + Sync),
+  /// Returns true once the user requested to cancel.
+  pub cancelled: &'a (dyn Fn() -> bool + Sync),
+}
+
+pub trait Module: Sync {
+  fn version(&self) -> String;
+  fn arguments(&self) -> ArgumentList<'static>;
+}
+
+pub trait Generator: Module {
+  /// Short name for file names, e.g. `builtin-energy`.
+  fn name(&self) -> String;
+  fn generate(&self, input: &str, args: &[ArgumentResult], callbacks: &Callbacks) -> Generation;
+}
+
+/// The render module can write audio-only files. It picks the codec from the output extension (m4a, opus or mp3).
+pub const CAPABILITY_AUDIO_ONLY: u32 = 1;
 
-type InitFunc<'a> = Symbol<'a, unsafe extern fn(ErrorCallback) -> ()>;
-type VersionFunc<'a> = Symbol<'a, unsafe extern fn(ErrorCallback) -> *const c_char>;
-type GetArgumentsFunc<'a> = Symbol<'a, unsafe extern fn(ErrorCallback) -> CArgumentList>;
-type RenderFunc<'a> = Symbol<'a, unsafe extern fn(*const c_char, *const c_char, CutList, CArgumentResultList, ProgressCallback, ErrorCallback) -> c_void>;
-type GenerateFunc<'a> = Symbol<'a, unsafe extern fn(*const c_char, CArgumentResultList, ProgressCallback, ErrorCallback) -> GeneratorResult>;
+pub trait Renderer: Module {
+  fn render(&self, input: &str, output: &str, cuts: &[Cut], args: &[ArgumentResult], callbacks: &Callbacks);
+  /// Bit set of `CAPABILITY_*` flags.
+  fn capabilities(&self) -> u32;
+  /// Whether an input in `container` with streams in `codecs` can be rendered to `output_format` (a file extension).
+  /// `codecs` is empty if the input could not be probed.
+  fn supports(&self, container: &str, codecs: &[String], output_format: &str) -> bool;
+}
 
-unsafe extern fn module_error_callback(message: *const c_char) {
-  raise_error(&std::ffi::CStr::from_ptr(message).to_string_lossy());
+/// A module loaded from a dynamic library speaking the C ABI.
+struct DynamicModule {
+  lib: Library,
 }
 
-pub fn load_render() -> Library {
+// The C ABI has no user data pointer, so the callbacks of the running module call are kept here.
+struct ActiveCallbacks(*const Callbacks<'static>);
+
+unsafe impl Send for ActiveCallbacks {}
+
+static ACTIVE_CALLBACKS: Mutex<Option<ActiveCallbacks>> = Mutex::new(None);
+
+fn with_callbacks<T, F: FnOnce() -> T>(callbacks: &Callbacks, f: F) -> T {
+  if let Ok(mut active) = ACTIVE_CALLBACKS.lock() {
+    *active = Some(ActiveCallbacks((callbacks as *const Callbacks).cast::<Callbacks<'static>>()));
+  }
+  let result = f();
+  if let Ok(mut active) = ACTIVE_CALLBACKS.lock() {
+    *active = None;
+  }
+  result
+}
+
+unsafe extern "C" fn module_progress_callback(name: *const c_char, value: c_double) {
+  if let Ok(name) = CStr::from_ptr(name).to_str() {
+    if let Ok(active) = ACTIVE_CALLBACKS.lock() {
+      if let Some(callbacks) = active.as_ref() {
+        ((*callbacks.0).progress)(name, value);
+      }
+    }
+  }
+}
+
+unsafe extern "C" fn module_cancel_callback() -> bool {
+  if let Ok(active) = ACTIVE_CALLBACKS.lock() {
+    if let Some(callbacks) = active.as_ref() {
+      return ((*callbacks.0).cancelled)();
+    }
+  }
+  false
+}
+
+impl Module for DynamicModule {
+  fn version(&self) -> String {
+    module_version(&self.lib)
+  }
+
+  fn arguments(&self) -> ArgumentList<'static> {
+    module_get_arguments(&self.lib)
+  }
+}
+
+impl Generator for DynamicModule {
+  fn name(&self) -> String {
+    generator_name(&self.lib)
+  }
+
+  fn generate(&self, input: &str, args: &[ArgumentResult], callbacks: &Callbacks) -> Generation {
+    let result = with_callbacks(callbacks, || {
+      generator_generate(&self.lib, input, args.to_vec().into(), module_progress_callback, module_cancel_callback)
+    });
+    raise_module_error();
+    Generation {
+      cuts: result.cuts.to_vec(),
+      stats: result.stats,
+    }
+  }
+}
+
+impl Renderer for DynamicModule {
+  fn render(&self, input: &str, output: &str, cuts: &[Cut], args: &[ArgumentResult], callbacks: &Callbacks) {
+    with_callbacks(callbacks, || {
+      render_render(&self.lib, input, output, cuts.into(), args.to_vec().into(), module_progress_callback, module_cancel_callback)
+    });
+    raise_module_error();
+  }
+
+  fn capabilities(&self) -> u32 {
+    render_capabilities(&self.lib)
+  }
+
+  fn supports(&self, container: &str, codecs: &[String], output_format: &str) -> bool {
+    render_supports(&self.lib, container, codecs.join(",").as_str(), output_format)
+  }
+}
+
+// Unwinding through the C ABI aborts, so recoverable module errors are kept here
+// until the module returns and raised afterwards.
+static MODULE_ERROR: Mutex<Option<String>> = Mutex::new(None);
+
+unsafe extern "C" fn module_error_callback(message: *const c_char) {
+  let message = std::ffi::CStr::from_ptr(message).to_string_lossy().to_string();
+  if errors_are_recoverable() {
+    if let Ok(mut error) = MODULE_ERROR.lock() {
+      error.get_or_insert(message);
+    }
+    return;
+  }
+  raise_error(&message);
+}
+
+/// Raises the first error a module reported during the last call.
+fn raise_module_error() {
+  let error = MODULE_ERROR.lock().ok().and_then(|mut error| error.take());
+  if let Some(message) = error {
+    raise_error(&message);
+  }
+}
+
+pub fn load_render() -> Box<dyn Renderer> {
   // load render so from modules/render.so (render.dll)
   let mut lib_path = "modules/librender.so";
   if cfg!(windows) {
@@ -169,17 +348,42 @@ pub fn load_render() -> Library {
     init(module_error_callback);
   }
   
-  lib
+  Box::new(DynamicModule { lib })
 }
 
-pub fn render_render(lib: &Library, input: &str, output: &str, cuts: CutList, args: CArgumentResultList, progress: ProgressCallback) {
+fn render_render(lib: &Library, input: &str, output: &str, cuts: CutList, args: CArgumentResultList, progress: ProgressCallback, cancel: CancelCallback) {
   let render: RenderFunc = unsafe { lib.get(b"render").unwrap() };
   let input = CString::new(input).unwrap();
   let output = CString::new(output).unwrap();
-  unsafe { render(input.as_ptr(), output.as_ptr(), cuts, args, progress, module_error_callback) };
+  unsafe { render(input.as_ptr(), output.as_ptr(), cuts, args, progress, module_error_callback, cancel) };
 }
 
-pub fn load_generator() -> Library {
+fn render_capabilities(lib: &Library) -> u32 {
+  // older render modules do not export capabilities and support none of them
+  let capabilities: CapabilitiesFunc = match unsafe { lib.get(b"capabilities") } {
+    Ok(capabilities) => capabilities,
+    Err(_) => return 0,
+  };
+  unsafe { capabilities(module_error_callback) }
+}
+
+fn render_supports(lib: &Library, container: &str, codecs: &str, output_format: &str) -> bool {
+  // older render modules can not tell, so they are trusted with everything
+  let supports: SupportsFunc = match unsafe { lib.get(b"supports") } {
+    Ok(supports) => supports,
+    Err(_) => return true,
+  };
+  let container = CString::new(container).unwrap();
+  let codecs = CString::new(codecs).unwrap();
+  let output_format = CString::new(output_format).unwrap();
+  unsafe { supports(container.as_ptr(), codecs.as_ptr(), output_format.as_ptr(), module_error_callback) }
+}
+
+pub fn load_generator(name: &str) -> Box<dyn Generator> {
+  if name == "builtin-energy" {
+    return Box::new(EnergyGenerator);
+  }
+
   // load generator so from modules/generator.so (generator.dll)
   
   let mut lib_path = "modules/libgenerator.so";
@@ -194,7 +398,8 @@ pub fn load_generator() -> Library {
   lib_path = binding.to_str().unwrap();
 
   if !Path::new(lib_path).exists() {
-    raise_error(format!("{} does not exist. Please compile the generator module first.", lib_path).as_str());
+    print_builtin_generator_warning(lib_path);
+    return Box::new(EnergyGenerator);
   }
   
   let lib = unsafe { Library::new(lib_path).unwrap() };
@@ -205,23 +410,33 @@ pub fn load_generator() -> Library {
     init(module_error_callback);
   }
 
-  lib
+  Box::new(DynamicModule { lib })
 }
 
-pub fn generator_generate(lib: &Library, input: &str, args: CArgumentResultList, progress: ProgressCallback) -> GeneratorResult {
+fn generator_generate(lib: &Library, input: &str, args: CArgumentResultList, progress: ProgressCallback, cancel: CancelCallback) -> GeneratorResult {
   let generate: GenerateFunc = unsafe { lib.get(b"generate").unwrap() };
   let input = CString::new(input).unwrap();
-  unsafe { generate(input.as_ptr(), args, progress, module_error_callback) }
+  unsafe { generate(input.as_ptr(), args, progress, module_error_callback, cancel) }
+}
+
+fn generator_name(lib: &Library) -> String {
+  // generators without a name export are known by the name they were selected with
+  let name: NameFunc = match unsafe { lib.get(b"name") } {
+    Ok(name) => name,
+    Err(_) => return selected_generator(),
+  };
+  let name = unsafe { CStr::from_ptr(name(module_error_callback)) };
+  name.to_string_lossy().to_string()
 }
 
-pub fn module_version(lib: &Library) -> String {
+fn module_version(lib: &Library) -> String {
   let version: VersionFunc = unsafe { lib.get(b"version").unwrap() };
   let version = unsafe { version(module_error_callback) };
   let version = unsafe { CStr::from_ptr(version) };
   version.to_str().unwrap().to_string()
 }
 
-pub fn module_get_arguments(lib: &Library) -> ArgumentList {
+fn module_get_arguments(lib: &Library) -> ArgumentList<'static> {
   let get_arguments: GetArgumentsFunc = unsafe { lib.get(b"get_arguments").unwrap() };
   unsafe { get_arguments(module_error_callback).into() }
-}
\ No newline at end of file
+}
@@ -27,8 +27,10 @@ use self::tabled::builder::Builder;
 use self::console::measure_text_width;
 use self::console::pad_str;
 
-use crate::helper::make_clickable_link;
-use crate::module_manager::GeneratorStats;
+use crate::helper::{is_audio_file, make_clickable_link};
+use crate::timeline::{format_timestamp, kept_length, segments, CutChange, Evaluation};
+use crate::module_manager::{Cut, Generation, GeneratorStats};
+use crate::queue::{JobStatus, QueuedJob};
 
 use self::ellipse::Ellipse;
 
@@ -55,7 +57,7 @@ pub fn greetings(render_version: &str, generator_version: &str) {
   let l7_left = make_clickable_link("Source", "https://github.com/Gamer92000/LectureCut/") + " - Made with ❤️ by " + &make_clickable_link("Gamer92000", "https://github.com/Gamer92000");
   let l7_right = format!("Generator: {} | Render: {}", style(generator_version).yellow(), style(render_version).yellow());
   let l7_right_width = measure_text_width(l7_right.as_str());
-  let l7 = l7_left + &" ".repeat(49 - l7_right_width) + &l7_right;
+  let l7 = l7_left + &" ".repeat(49_usize.saturating_sub(l7_right_width)) + &l7_right;
 
   // center text
   let term_width = term.size().1;
@@ -68,7 +70,7 @@ pub fn greetings(render_version: &str, generator_version: &str) {
   }
   
   // manually center l7, because console does not support links...
-  let padding = term.size().1 - 85;
+  let padding = term.size().1.saturating_sub(85);
   let l7_padded = " ".repeat((padding / 2).into()) + &l7;
   if let Err(e) = term.write_line(&l7_padded) {
     println!("Error: {}", e);
@@ -80,7 +82,39 @@ pub fn greetings(render_version: &str, generator_version: &str) {
 }
 
 
+thread_local! {
+  // set while `catch_errors` runs
+  static RECOVERABLE: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+/// An error raised while `catch_errors` is running.
+struct RaisedError(String);
+
+pub fn errors_are_recoverable() -> bool {
+  RECOVERABLE.with(|recoverable| recoverable.get())
+}
+
+/// Runs `f` and returns the message of an error raised meanwhile instead of exiting.
+/// The long running modes use this, so a single broken file does not stop them.
+pub fn catch_errors<T, F: FnOnce() -> T>(f: F) -> Result<T, String> {
+  let previous = RECOVERABLE.with(|recoverable| recoverable.replace(true));
+  let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f));
+  RECOVERABLE.with(|recoverable| recoverable.set(previous));
+  match result {
+    Ok(value) => Ok(value),
+    Err(payload) => match payload.downcast::<RaisedError>() {
+      Ok(error) => Err(error.0),
+      Err(payload) => std::panic::resume_unwind(payload),
+    },
+  }
+}
+
 pub fn raise_error(message: &str) {
+  // unwinding does not run the panic hook, so nothing is printed here
+  if errors_are_recoverable() {
+    std::panic::resume_unwind(Box::new(RaisedError(message.to_string())));
+  }
+
   let term = Term::stderr();
   if let Err(e) = term.write_line(&format!("{}: {}", style("Error").red(), message)) {
     println!("Error: {}", e);
@@ -92,13 +126,119 @@ pub fn raise_error(message: &str) {
   std::process::exit(1);
 }
 
-pub fn print_non_mp4_warning() {
+pub fn print_input_warning(message: &str) {
+  let term = Term::stderr();
+  if let Err(e) = term.write_line(&format!("{}: {}", style("⚠️").yellow(), message)) {
+    println!("Error: {}", e);
+  }
+}
+
+pub fn print_builtin_generator_warning(missing_module: &str) {
+  let term = Term::stderr();
+  if let Err(e) = term.write_line(&format!("{}: {} does not exist. Falling back to the built-in energy generator.", style("⚠️").yellow(), missing_module)) {
+    println!("Error: {}", e);
+  }
+}
+
+pub fn print_cancelling() {
   let term = Term::stderr();
-  if let Err(e) = term.write_line(&format!("{}: {}", style("⚠️").yellow(), "The input file is not an MP4 file. This may cause issues.")) {
+  if let Err(e) = term.write_line(&format!("{}: {}", style("⚠️").yellow(), "Cancelling... Press Ctrl-C again to abort immediately.")) {
     println!("Error: {}", e);
   }
 }
 
+pub fn print_cancelled() {
+  let term = Term::stderr();
+  if let Err(e) = term.write_line(&format!("{}: {}", style("Cancelled").red(), "Partial output has been removed.")) {
+    println!("Error: {}", e);
+  }
+}
+
+pub fn print_mapped_time(from: &str, time: f64, to: &str, mapped: f64, kept: bool) {
+  let term = Term::stdout();
+  if let Err(e) = term.write_line(&format!("{} {} -> {} {}", from, style(format_timestamp(time)).yellow(), to, style(format_timestamp(mapped)).green())) {
+    println!("Error: {}", e);
+  }
+  if !kept {
+    if let Err(e) = term.write_line(&format!("{}: {}", style("⚠️").yellow(), "This part was removed, the output continues at the given timestamp.")) {
+      println!("Error: {}", e);
+    }
+  }
+}
+
+/// Upper bounds in seconds of the segment length histogram buckets.
+const HISTOGRAM_BUCKETS: [(f64, &str); 6] = [
+  (1.0, "< 1 s"),
+  (5.0, "1-5 s"),
+  (15.0, "5-15 s"),
+  (60.0, "15-60 s"),
+  (300.0, "1-5 min"),
+  (f64::INFINITY, "> 5 min"),
+];
+
+/// Prints a bar of the whole input with kept and removed parts, the longest removed gaps
+/// and a histogram of the kept segment lengths.
+pub fn print_timeline(cuts: &[Cut], duration: f64) {
+  let term = Term::stdout();
+  let duration = cuts.last().map(|cut| cut.end.max(duration)).unwrap_or(duration);
+  let segments = segments(cuts, duration);
+  if duration <= 0.0 {
+    return;
+  }
+
+  // every character covers the same amount of time and shows whether most of it is kept
+  let width = (term.size().1 as usize).saturating_sub(4).clamp(20, 100);
+  let mut bar = String::new();
+  for i in 0..width {
+    let from = duration * i as f64 / width as f64;
+    let to = duration * (i + 1) as f64 / width as f64;
+    let kept: f64 = cuts.iter().map(|cut| (cut.end.min(to) - cut.start.max(from)).max(0.0)).sum();
+    if kept * 2.0 >= to - from {
+      bar += &style("█").green().to_string();
+    } else {
+      bar += &style("░").red().to_string();
+    }
+  }
+
+  let mut lines = vec![
+    String::new(),
+    format!("  {}", bar),
+    format!("  {}{}{}", format_timestamp(0.0), " ".repeat(width.saturating_sub(24)), format_timestamp(duration)),
+    format!("  {} kept  {} removed", style("█").green(), style("░").red()),
+    String::new(),
+  ];
+
+  let mut gaps: Vec<_> = segments.iter().filter(|segment| !segment.kept).collect();
+  gaps.sort_by(|a, b| b.length().total_cmp(&a.length()));
+  if !gaps.is_empty() {
+    lines.push(format!("  {}", style("Longest removed gaps").bold()));
+    for (i, gap) in gaps.iter().take(5).enumerate() {
+      lines.push(format!("  {}. {} - {}  {}", i + 1, format_timestamp(gap.start), format_timestamp(gap.end), style(format!("{:.1} s", gap.length())).red()));
+    }
+    lines.push(String::new());
+  }
+
+  let mut counts = [0_usize; HISTOGRAM_BUCKETS.len()];
+  for segment in segments.iter().filter(|segment| segment.kept) {
+    if let Some(bucket) = HISTOGRAM_BUCKETS.iter().position(|(limit, _)| segment.length() < *limit) {
+      counts[bucket] += 1;
+    }
+  }
+  let max_count = counts.iter().copied().max().unwrap_or(0).max(1);
+  lines.push(format!("  {}", style("Kept segment lengths").bold()));
+  for ((_, label), count) in HISTOGRAM_BUCKETS.iter().zip(counts) {
+    let bar_width = (count * 30).div_ceil(max_count);
+    lines.push(format!("  {:>8} {} {}", label, style("■".repeat(bar_width)).cyan(), count));
+  }
+  lines.push(String::new());
+
+  for line in lines {
+    if let Err(e) = term.write_line(&line) {
+      println!("Error: {}", e);
+    }
+  }
+}
+
 pub fn print_dir_not_empty_warning() {
   let term = Term::stderr();
   if let Err(e) = term.write_line(&format!("{}: {}", style("⚠️").yellow(), "The output directory is not empty. Existing files will be skipped.\n")) {
@@ -106,6 +246,182 @@ pub fn print_dir_not_empty_warning() {
   }
 }
 
+pub fn print_watching(dir: &str, output: &str, archive: &str) {
+  let term = Term::stdout();
+  let lines = [
+    format!("Watching: {}", style(dir).yellow()),
+    format!("  Output: {}", style(output).yellow()),
+    format!(" Archive: {}", style(archive).yellow()),
+    "".to_string(),
+  ];
+  for line in lines {
+    if let Err(e) = term.write_line(&line) {
+      println!("Error: {}", e);
+    }
+  }
+}
+
+pub fn print_serving(bind: &str) {
+  let term = Term::stdout();
+  if let Err(e) = term.write_line(&format!("Serving job API on {}\n", style(format!("http://{}", bind)).yellow())) {
+    println!("Error: {}", e);
+  }
+}
+
+pub fn print_queue(jobs: &[QueuedJob]) {
+  let mut builder = Builder::from(
+    vec![vec![
+      String::from("Id"),
+      String::from("Priority"),
+      String::from("Status"),
+      String::from("Input File"),
+      String::from("Output File"),
+    ]]
+  );
+
+  for job in jobs {
+    let status = match job.status {
+      JobStatus::Queued => "queued",
+      JobStatus::Running => "running",
+      JobStatus::Done => "done",
+      JobStatus::Cancelled => "cancelled",
+      JobStatus::Failed => "failed",
+    };
+    builder.push_record([
+      job.id.to_string(),
+      job.priority.to_string(),
+      status.to_string(),
+      job.input.clone(),
+      job.output.clone(),
+    ]);
+  }
+
+  let mut binding = builder.build();
+  let table = binding
+    .with(Style::rounded())
+    .with(Modify::new(Rows::single(0))
+        .with(Alignment::center()))
+    .with(Modify::new(Columns::new(0..2).not(Rows::first()))
+        .with(Alignment::right()))
+    .with(Colorization::exact([Color::new("\x1b[1m", "\x1b[0m") | Color::FG_BRIGHT_YELLOW], Rows::first()))
+    .with(Colorization::exact([Color::FG_CYAN], Columns::single(0).not(Rows::first())))
+    .with(Colorization::exact([Color::FG_MAGENTA], Columns::single(2).not(Rows::first())));
+
+  println!("{}", table);
+}
+
+fn format_cuts(cuts: &[Cut]) -> String {
+  cuts.iter().map(|cut| format!("{} - {}", format_timestamp(cut.start), format_timestamp(cut.end))).collect::<Vec<_>>().join("\n")
+}
+
+fn format_difference(seconds: f64) -> String {
+  format!("{:+.2} s", seconds)
+}
+
+pub fn print_diff(changes: &[CutChange], kept_before: f64, kept_after: f64, overlap: f64) {
+  let mut builder = Builder::from(
+    vec![vec![
+      String::from("Change"),
+      String::from("Kept Before"),
+      String::from("Kept After"),
+      String::from("Difference"),
+    ]]
+  );
+
+  let mut unchanged = 0;
+  for change in changes {
+    if change.is_unchanged() {
+      unchanged += 1;
+      continue;
+    }
+    let kind = match (change.before.is_empty(), change.after.is_empty()) {
+      (true, _) => "added",
+      (_, true) => "removed",
+      _ => "changed",
+    };
+    builder.push_record([
+      kind.to_string(),
+      format_cuts(&change.before),
+      format_cuts(&change.after),
+      format_difference(kept_length(&change.after) - kept_length(&change.before)),
+    ]);
+  }
+
+  let mut binding = builder.build();
+  let table = binding
+    .with(Style::rounded())
+    .with(Modify::new(Rows::single(0))
+        .with(Alignment::center()))
+    .with(Modify::new(Columns::single(3).not(Rows::first()))
+        .with(Alignment::right()))
+    .with(Colorization::exact([Color::new("\x1b[1m", "\x1b[0m") | Color::FG_BRIGHT_YELLOW], Rows::first()))
+    .with(Colorization::exact([Color::FG_MAGENTA], Columns::single(0).not(Rows::first())))
+    .with(Colorization::exact([Color::FG_CYAN], Columns::single(3).not(Rows::first())));
+
+  if changes.len() > unchanged {
+    println!("{}", table);
+  } else {
+    println!("The cut lists keep the same segments.");
+  }
+  println!("Unchanged segments: {}", unchanged);
+  println!("Kept: {} -> {} ({})", format_timestamp(kept_before), format_timestamp(kept_after), style(format_difference(kept_after - kept_before)).cyan());
+  println!("Overlap: {}", style(format!("{:.2} %", overlap * 100.0)).green());
+}
+
+pub fn print_evaluation(evaluation: &Evaluation) {
+  let mut builder = Builder::from(vec![vec![String::from("Metric"), String::from("Value")]]);
+  builder.push_record([String::from("Precision"), format!("{:.2} %", evaluation.precision * 100.0)]);
+  builder.push_record([String::from("Recall"), format!("{:.2} %", evaluation.recall * 100.0)]);
+  builder.push_record([String::from("F1"), format!("{:.4}", evaluation.f1)]);
+  builder.push_record([String::from("Speech removed"), format!("{:.2} s", evaluation.false_removal)]);
+  builder.push_record([String::from("Silence kept"), format!("{:.2} s", evaluation.false_keep)]);
+  builder.push_record([String::from("Boundary error"), match evaluation.boundary_error {
+    Some((mean, max)) => format!("{:.3} s mean, {:.3} s max", mean, max),
+    None => String::from("-"),
+  }]);
+
+  let mut binding = builder.build();
+  let table = binding
+    .with(Style::rounded())
+    .with(Modify::new(Rows::single(0))
+        .with(Alignment::center()))
+    .with(Modify::new(Columns::single(1).not(Rows::first()))
+        .with(Alignment::right()))
+    .with(Colorization::exact([Color::new("\x1b[1m", "\x1b[0m") | Color::FG_BRIGHT_YELLOW], Rows::first()))
+    .with(Colorization::exact([Color::FG_CYAN], Columns::single(1).not(Rows::first())));
+
+  println!("{}", table);
+}
+
+pub fn print_sweep(names: &[String], rows: &[(Vec<String>, Generation)]) {
+  let mut header = names.to_vec();
+  header.extend([String::from("Kept"), String::from("Kept %"), String::from("Segments")]);
+  let mut builder = Builder::from(vec![header]);
+
+  for (values, gen) in rows {
+    let mut record = values.clone();
+    let percent = if gen.stats.len_pre_cut > 0.0 { gen.stats.len_post_cut / gen.stats.len_pre_cut * 100.0 } else { 0.0 };
+    record.extend([
+      format_timestamp(gen.stats.len_post_cut),
+      format!("{:.2} %", percent),
+      gen.cuts.len().to_string(),
+    ]);
+    builder.push_record(record);
+  }
+
+  let mut binding = builder.build();
+  let table = binding
+    .with(Style::rounded())
+    .with(Modify::new(Rows::single(0))
+        .with(Alignment::center()))
+    .with(Modify::new(Rows::new(1..))
+        .with(Alignment::right()))
+    .with(Colorization::exact([Color::new("\x1b[1m", "\x1b[0m") | Color::FG_BRIGHT_YELLOW], Rows::first()))
+    .with(Colorization::exact([Color::FG_CYAN], Columns::new(names.len()..).not(Rows::first())));
+
+  println!("{}", table);
+}
+
 pub fn print_stats(files: Vec<(String, String, GeneratorStats)>, time_used: Duration) {
   let mut builder = Builder::from(
     vec![vec![
@@ -135,17 +451,16 @@ pub fn print_stats(files: Vec<(String, String, GeneratorStats)>, time_used: Dura
       0.0
     };
 
-    let size_change_str = format!("{:.2} MB -> {:.2} MB", input_size, output_size);
+    // audio-only outputs are marked, as their size is not comparable to a video
+    let output_kind = if is_audio_file(output_file.as_str()) { " audio" } else { "" };
+
+    let size_change_str = format!("{:.2} MB -> {:.2} MB{}", input_size, output_size, output_kind);
 
-    let duration_change_str = format!("{} min {} sec -> {} min {} sec", (input_length / 60.0) as i8, (input_length % 60.0) as i8, (output_length / 60.0) as i8, (output_length % 60.0)as i8);
+    let duration_change_str = format!("{} min {} sec -> {} min {} sec{}", (input_length / 60.0) as i8, (input_length % 60.0) as i8, (output_length / 60.0) as i8, (output_length % 60.0)as i8, output_kind);
 
     let length_change_percent_str = format!("{:.2} %", (output_length / input_length) * 100.0);
 
-    let intput_file_str = if let Some(filename) = input_file.split(path::MAIN_SEPARATOR).last() {
-      filename
-    } else {
-      "Unknown"
-    };
+    let intput_file_str = input_file.split(path::MAIN_SEPARATOR).next_back().unwrap_or("Unknown");
     intput_file_str.truncate_ellipse(20);
 
     builder.push_record([
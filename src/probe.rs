@@ -0,0 +1,268 @@
+extern crate serde;
+extern crate serde_json;
+extern crate tree_magic;
+
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use self::serde::Deserialize;
+
+/// A single stream of a media file.
+#[derive(Clone)]
+pub struct Stream {
+  pub index: u32,
+  /// `video`, `audio`, `subtitle`, `data` or `attachment`
+  pub kind: String,
+  pub codec: String,
+}
+
+/// What is known about a media file.
+///
+/// The file is probed with ffprobe. If ffprobe is not installed or fails,
+/// only the MIME type is sniffed and the streams and the duration stay unknown.
+#[derive(Clone)]
+pub struct MediaInfo {
+  /// Container format, e.g. `mp4` or `matroska`.
+  pub container: String,
+  pub duration: Option<f64>,
+  /// Only a placeholder video stream if the file could not be probed.
+  pub streams: Vec<Stream>,
+  probed: bool,
+}
+
+#[derive(Deserialize)]
+struct FfprobeOutput {
+  #[serde(default)]
+  streams: Vec<FfprobeStream>,
+  format: Option<FfprobeFormat>,
+}
+
+#[derive(Deserialize)]
+struct FfprobeStream {
+  index: u32,
+  #[serde(default)]
+  codec_type: String,
+  #[serde(default)]
+  codec_name: String,
+  #[serde(default)]
+  disposition: FfprobeDisposition,
+}
+
+#[derive(Deserialize, Default)]
+struct FfprobeDisposition {
+  // cover art of audio files shows up as a video stream
+  #[serde(default)]
+  attached_pic: u8,
+}
+
+#[derive(Deserialize)]
+struct FfprobeFormat {
+  #[serde(default)]
+  format_name: String,
+  duration: Option<String>,
+}
+
+/// Container formats the render modules are built for.
+const SUPPORTED_CONTAINERS: [&str; 1] = ["mp4"];
+
+fn ffprobe(path: &Path) -> Option<MediaInfo> {
+  let output = Command::new("ffprobe")
+    .args(["-v", "error", "-print_format", "json", "-show_format", "-show_streams"])
+    .arg(path)
+    .stdin(Stdio::null())
+    .stderr(Stdio::null())
+    .output()
+    .ok()?;
+  if !output.status.success() {
+    return None;
+  }
+  parse_ffprobe(&output.stdout)
+}
+
+/// Maps the JSON printed by ffprobe to what the host needs to know.
+fn parse_ffprobe(json: &[u8]) -> Option<MediaInfo> {
+  let probe: FfprobeOutput = serde_json::from_slice(json).ok()?;
+  let format = probe.format?;
+
+  Some(MediaInfo {
+    // ffprobe lists every name of the demuxer, e.g. "mov,mp4,m4a,3gp,3g2,mj2"
+    container: match format.format_name.split(',').find(|name| SUPPORTED_CONTAINERS.contains(name)) {
+      Some(name) => name.to_string(),
+      None => format.format_name.split(',').next().unwrap_or_default().to_string(),
+    },
+    duration: format.duration.and_then(|duration| duration.parse().ok()),
+    streams: probe.streams.into_iter()
+      .filter(|stream| stream.disposition.attached_pic == 0)
+      .map(|stream| Stream {
+        index: stream.index,
+        kind: stream.codec_type,
+        codec: stream.codec_name,
+      })
+      .collect(),
+    probed: true,
+  })
+}
+
+fn sniff(path: &Path) -> MediaInfo {
+  let mime = tree_magic::from_filepath(path);
+  let container = match mime.as_str() {
+    "video/mp4" => "mp4",
+    "video/x-matroska" => "matroska",
+    "video/webm" => "webm",
+    "video/quicktime" => "mov",
+    _ => mime.split('/').next_back().unwrap_or_default(),
+  };
+  // without ffprobe, a video MIME type is all there is to go by
+  let streams = if mime.starts_with("video") {
+    vec![Stream { index: 0, kind: "video".to_string(), codec: "unknown".to_string() }]
+  } else {
+    Vec::new()
+  };
+  MediaInfo {
+    container: container.to_string(),
+    duration: None,
+    streams,
+    probed: false,
+  }
+}
+
+impl MediaInfo {
+  /// Audio read from stdin, which is stored as a mono 16 bit WAV file before processing.
+  pub fn stdin_audio() -> MediaInfo {
+    MediaInfo {
+      container: "wav".to_string(),
+      duration: None,
+      streams: vec![Stream { index: 0, kind: "audio".to_string(), codec: "pcm_s16le".to_string() }],
+      probed: true,
+    }
+  }
+
+  fn count(&self, kind: &str) -> usize {
+    self.streams.iter().filter(|stream| stream.kind == kind).count()
+  }
+
+  /// Whether the file has a video stream, i.e. whether it should be processed.
+  pub fn is_video(&self) -> bool {
+    self.count("video") > 0
+  }
+
+  /// Codec names of all streams, empty if the file could not be probed.
+  pub fn codecs(&self) -> Vec<String> {
+    if !self.probed {
+      return Vec::new();
+    }
+    self.streams.iter().map(|stream| stream.codec.clone()).collect()
+  }
+
+  /// Describes everything about the file that may lead to a surprising result.
+  pub fn warnings(&self) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if !SUPPORTED_CONTAINERS.contains(&self.container.as_str()) {
+      warnings.push(format!("The input is a {} file, not MP4. This may cause issues.", self.container));
+    }
+    if !self.probed {
+      return warnings;
+    }
+    if !self.is_video() {
+      warnings.push("The input has no video stream.".to_string());
+    }
+    match self.count("audio") {
+      0 => warnings.push("The input has no audio stream, so no speech can be detected.".to_string()),
+      1 => {},
+      n => warnings.push(format!("The input has {} audio streams, only the first one is analysed.", n)),
+    }
+    for stream in &self.streams {
+      if stream.kind != "video" && stream.kind != "audio" {
+        warnings.push(format!("Stream #{} ({} {}) is not supported and will be dropped.", stream.index, stream.kind, stream.codec));
+      }
+    }
+    warnings
+  }
+}
+
+/// Probes a media file with ffprobe, falling back to MIME sniffing.
+pub fn probe(path: &Path) -> MediaInfo {
+  ffprobe(path).unwrap_or_else(|| sniff(path))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn stream(index: u32, kind: &str, codec: &str) -> Stream {
+    Stream { index, kind: kind.to_string(), codec: codec.to_string() }
+  }
+
+  fn probed(container: &str, streams: Vec<Stream>) -> MediaInfo {
+    MediaInfo { container: container.to_string(), duration: Some(60.0), streams, probed: true }
+  }
+
+  #[test]
+  fn ffprobe_output() {
+    let info = parse_ffprobe(br#"{
+      "streams": [
+        { "index": 0, "codec_type": "video", "codec_name": "h264" },
+        { "index": 1, "codec_type": "audio", "codec_name": "aac" },
+        { "index": 2, "codec_type": "video", "codec_name": "mjpeg", "disposition": { "attached_pic": 1 } },
+        { "index": 3 }
+      ],
+      "format": { "format_name": "mov,mp4,m4a,3gp,3g2,mj2", "duration": "120.500000" }
+    }"#).unwrap();
+    assert_eq!(info.container, "mp4");
+    assert_eq!(info.duration, Some(120.5));
+    assert_eq!(info.codecs(), ["h264", "aac", ""]);
+    assert_eq!(info.streams.iter().map(|stream| stream.index).collect::<Vec<_>>(), [0, 1, 3]);
+    assert!(info.is_video());
+  }
+
+  #[test]
+  fn ffprobe_output_without_details() {
+    let info = parse_ffprobe(br#"{ "format": { "format_name": "matroska,webm", "duration": "N/A" } }"#).unwrap();
+    assert_eq!(info.container, "matroska");
+    assert_eq!(info.duration, None);
+    assert!(info.streams.is_empty());
+    assert!(!info.is_video());
+
+    assert!(parse_ffprobe(br#"{ "streams": [] }"#).is_none());
+    assert!(parse_ffprobe(b"not json").is_none());
+  }
+
+  #[test]
+  fn no_warnings_for_a_plain_lecture() {
+    let info = probed("mp4", vec![stream(0, "video", "h264"), stream(1, "audio", "aac")]);
+    assert!(info.warnings().is_empty());
+  }
+
+  #[test]
+  fn warnings_for_surprising_inputs() {
+    let info = probed("matroska", vec![
+      stream(0, "video", "h264"),
+      stream(1, "audio", "aac"),
+      stream(2, "audio", "opus"),
+      stream(3, "subtitle", "subrip"),
+    ]);
+    assert_eq!(info.warnings(), [
+      "The input is a matroska file, not MP4. This may cause issues.",
+      "The input has 2 audio streams, only the first one is analysed.",
+      "Stream #3 (subtitle subrip) is not supported and will be dropped.",
+    ]);
+
+    let info = probed("mp4", vec![stream(0, "audio", "aac")]);
+    assert_eq!(info.warnings(), ["The input has no video stream."]);
+
+    let info = probed("mp4", vec![stream(0, "video", "h264")]);
+    assert_eq!(info.warnings(), ["The input has no audio stream, so no speech can be detected."]);
+  }
+
+  #[test]
+  fn sniffed_files_only_warn_about_the_container() {
+    let info = MediaInfo {
+      container: "webm".to_string(),
+      duration: None,
+      streams: vec![stream(0, "video", "unknown")],
+      probed: false,
+    };
+    assert_eq!(info.warnings(), ["The input is a webm file, not MP4. This may cause issues."]);
+    assert!(info.codecs().is_empty());
+  }
+}
@@ -0,0 +1,237 @@
+extern crate dirs;
+extern crate serde;
+extern crate serde_json;
+
+use std::fs::{File, TryLockError};
+use std::io::{BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use self::serde::{Deserialize, Serialize};
+
+use crate::module_manager::{ArgumentResult, GeneratorStats};
+use crate::printer::raise_error;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+  Queued,
+  Running,
+  Done,
+  Cancelled,
+  Failed,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct QueuedJob {
+  pub id: u64,
+  pub priority: i64,
+  pub status: JobStatus,
+  pub input: String,
+  pub output: String,
+  pub tsonly: bool,
+  pub generator_args: Vec<ArgumentResult>,
+  pub render_args: Vec<ArgumentResult>,
+  pub stats: Option<GeneratorStats>,
+  #[serde(default)]
+  pub error: Option<String>,
+  /// Process id of the worker running the job.
+  #[serde(default)]
+  pub worker: Option<u32>,
+}
+
+/// A running `queue run`. Its lock file tells other workers that its jobs are still being processed.
+pub struct Worker {
+  path: PathBuf,
+  _lock: File,
+}
+
+impl Drop for Worker {
+  fn drop(&mut self) {
+    let _ = std::fs::remove_file(&self.path);
+  }
+}
+
+fn queue_dir() -> PathBuf {
+  // the queue is per user, as installed executables usually can not write next to themselves
+  let dir = match dirs::data_local_dir() {
+    Some(dir) => dir.join("lecturecut"),
+    None => {
+      raise_error("Could not find a data directory for the queue.");
+      return PathBuf::new();
+    }
+  };
+  if let Err(e) = std::fs::create_dir_all(dir.join("workers")) {
+    raise_error(format!("Could not create {}: {}", dir.display(), e).as_str());
+  }
+  dir
+}
+
+fn queue_path() -> PathBuf {
+  queue_dir().join("queue.json")
+}
+
+fn worker_lock_path(pid: u32) -> PathBuf {
+  queue_dir().join("workers").join(format!("{}.lock", pid))
+}
+
+/// Takes an exclusive lock on `path`, which is released once the returned file is dropped.
+fn lock(path: &Path) -> File {
+  match File::create(path).and_then(|file| file.lock().map(|_| file)) {
+    Ok(file) => file,
+    Err(e) => {
+      raise_error(format!("Could not lock {}: {}", path.display(), e).as_str());
+      unreachable!("raise_error does not return");
+    }
+  }
+}
+
+fn lock_queue() -> File {
+  lock(&queue_dir().join("queue.lock"))
+}
+
+/// Runs `f` on the jobs and saves them, while holding the queue lock.
+/// This keeps concurrent `queue` commands from overwriting each other's changes.
+fn with_jobs<T, F: FnOnce(&mut Vec<QueuedJob>) -> T>(f: F) -> T {
+  let _lock = lock_queue();
+  let mut jobs = read_jobs();
+  let result = f(&mut jobs);
+  save(&jobs);
+  result
+}
+
+pub fn load() -> Vec<QueuedJob> {
+  let _lock = lock_queue();
+  read_jobs()
+}
+
+fn read_jobs() -> Vec<QueuedJob> {
+  let path = queue_path();
+  if !path.exists() {
+    return Vec::new();
+  }
+  match File::open(&path).map(BufReader::new) {
+    Ok(reader) => match serde_json::from_reader(reader) {
+      Ok(jobs) => jobs,
+      Err(e) => {
+        raise_error(format!("{} is corrupted: {}", path.display(), e).as_str());
+        Vec::new()
+      }
+    },
+    Err(e) => {
+      raise_error(format!("Could not read {}: {}", path.display(), e).as_str());
+      Vec::new()
+    }
+  }
+}
+
+fn save(jobs: &[QueuedJob]) {
+  let path = queue_path();
+  // write to a temporary file first, so a crash never leaves a half written queue behind
+  let tmp_path = path.with_extension("json.tmp");
+  let result = File::create(&tmp_path)
+    .and_then(|mut file| {
+      let content = serde_json::to_string_pretty(jobs).map_err(std::io::Error::other)?;
+      file.write_all(content.as_bytes())?;
+      file.flush()
+    })
+    .and_then(|_| std::fs::rename(&tmp_path, &path));
+  if let Err(e) = result {
+    raise_error(format!("Could not write {}: {}", path.display(), e).as_str());
+  }
+}
+
+pub fn add(mut job: QueuedJob) -> u64 {
+  with_jobs(|jobs| {
+    if jobs.iter().any(|other| other.output == job.output && matches!(other.status, JobStatus::Queued | JobStatus::Running)) {
+      raise_error("Another queued job already writes to this output file.");
+    }
+    job.id = jobs.iter().map(|job| job.id + 1).max().unwrap_or(1);
+    jobs.push(job.clone());
+    job.id
+  })
+}
+
+fn update<F: FnOnce(&mut QueuedJob)>(id: u64, f: F) {
+  with_jobs(|jobs| {
+    match jobs.iter_mut().find(|job| job.id == id) {
+      Some(job) => f(job),
+      None => raise_error(format!("There is no job with id {}.", id).as_str()),
+    }
+  });
+}
+
+pub fn cancel(id: u64) {
+  update(id, |job| {
+    if job.status != JobStatus::Queued {
+      raise_error("Only queued jobs can be cancelled.");
+    }
+    job.status = JobStatus::Cancelled;
+  });
+}
+
+pub fn set_priority(id: u64, priority: i64) {
+  update(id, |job| {
+    if job.status != JobStatus::Queued {
+      raise_error("Only queued jobs can be reprioritized.");
+    }
+    job.priority = priority;
+  });
+}
+
+/// Registers the current process as a worker for as long as the returned value lives.
+pub fn register_worker() -> Worker {
+  let path = worker_lock_path(std::process::id());
+  let lock = lock(&path);
+  Worker { path, _lock: lock }
+}
+
+fn worker_alive(pid: u32) -> bool {
+  // a process id seen again is either this worker or a reused id, neither of them runs the job
+  if pid == std::process::id() {
+    return false;
+  }
+  match File::open(worker_lock_path(pid)) {
+    Ok(lock) => matches!(lock.try_lock(), Err(TryLockError::WouldBlock)),
+    Err(_) => false,
+  }
+}
+
+/// Marks jobs as queued again that were left running by a worker that is gone.
+pub fn requeue_interrupted() {
+  with_jobs(|jobs| {
+    for job in jobs.iter_mut().filter(|job| job.status == JobStatus::Running) {
+      if !job.worker.is_some_and(worker_alive) {
+        job.status = JobStatus::Queued;
+        job.worker = None;
+      }
+    }
+  });
+}
+
+/// Picks the queued job with the highest priority (oldest first on ties) and marks it as running.
+pub fn start_next() -> Option<QueuedJob> {
+  with_jobs(|jobs| {
+    let job = jobs.iter_mut()
+      .filter(|job| job.status == JobStatus::Queued)
+      .max_by(|a, b| a.priority.cmp(&b.priority).then(b.id.cmp(&a.id)))?;
+    job.status = JobStatus::Running;
+    job.worker = Some(std::process::id());
+    Some(job.clone())
+  })
+}
+
+pub fn finish(id: u64, stats: GeneratorStats) {
+  update(id, |job| {
+    job.status = JobStatus::Done;
+    job.worker = None;
+    job.stats = Some(stats);
+  });
+}
+
+pub fn fail(id: u64, error: String) {
+  update(id, |job| {
+    job.status = JobStatus::Failed;
+    job.worker = None;
+    job.error = Some(error);
+  });
+}
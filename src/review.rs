@@ -0,0 +1,124 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+use crate::module_manager::Generation;
+use crate::timeline::{format_timestamp, segments};
+
+fn escape_html(text: &str) -> String {
+  text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Builds a file:// URL, so the page finds the input wherever it is opened from.
+fn file_url(path: &Path) -> String {
+  let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+  let path = path.to_string_lossy().replace('\\', "/");
+  let mut url = String::from(if path.starts_with('/') { "file://" } else { "file:///" });
+  for byte in path.bytes() {
+    match byte {
+      b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' | b':' => url.push(byte as char),
+      _ => url += format!("%{:02X}", byte).as_str(),
+    }
+  }
+  url
+}
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em auto; max-width: 60em; color: #222; }
+video { width: 100%; background: #000; }
+#timeline { position: relative; height: 2.5em; margin: 1em 0; background: #ddd; cursor: pointer; }
+#timeline div { position: absolute; top: 0; bottom: 0; }
+#timeline .kept { background: #4caf50; }
+#timeline .removed { background: #e57373; }
+#playhead { width: 2px; background: #000; }
+table { border-collapse: collapse; width: 100%; }
+td, th { padding: 0.3em 0.6em; text-align: right; border-bottom: 1px solid #ddd; }
+tr.removed { color: #b71c1c; }
+";
+
+const SCRIPT: &str = "
+const video = document.getElementById('video');
+const timeline = document.getElementById('timeline');
+const playhead = document.getElementById('playhead');
+function jump(time) { video.currentTime = time; video.play(); }
+timeline.addEventListener('click', e => jump(e.offsetX / timeline.clientWidth * DURATION));
+video.addEventListener('timeupdate', () => playhead.style.left = (video.currentTime / DURATION * 100) + '%');
+";
+
+/// Checks that the review page at `path` overwrites neither the input, the output nor any other file.
+pub fn check_review_path(path: &Path, input: &Path, output: &Path) -> Result<(), String> {
+  let target = std::path::absolute(path).ok();
+  if target == std::path::absolute(input).ok() {
+    return Err(format!("The review page would overwrite the input {}.", input.display()));
+  }
+  if target == std::path::absolute(output).ok() {
+    return Err(format!("The review page would overwrite the output {}.", output.display()));
+  }
+  if path.exists() {
+    return Err(format!("Review page {} already exists.", path.display()));
+  }
+  Ok(())
+}
+
+/// Writes a self-contained HTML page showing the kept and removed parts of the input next to a player of the input.
+pub fn write_review_html(path: &Path, input: &str, output: &str, gen: &Generation) -> std::io::Result<()> {
+  let duration = match gen.cuts.last() {
+    Some(cut) => gen.stats.len_pre_cut.max(cut.end),
+    None => gen.stats.len_pre_cut,
+  };
+  let segments = segments(&gen.cuts, duration);
+  let percent = |time: f64| if duration > 0.0 { time / duration * 100.0 } else { 0.0 };
+
+  let mut timeline = String::new();
+  let mut rows = String::new();
+  for segment in &segments {
+    let kind = if segment.kept { "kept" } else { "removed" };
+    timeline += format!(
+      "<div class=\"{}\" style=\"left: {:.4}%; width: {:.4}%\" title=\"{} {} - {}\"></div>\n",
+      kind, percent(segment.start), percent(segment.length()), kind, format_timestamp(segment.start), format_timestamp(segment.end),
+    ).as_str();
+    rows += format!(
+      "<tr class=\"{}\"><td>{}</td><td>{}</td><td>{}</td><td>{:.2} s</td><td><button onclick=\"jump({})\">Play</button></td></tr>\n",
+      kind, kind, format_timestamp(segment.start), format_timestamp(segment.end), segment.length(), segment.start,
+    ).as_str();
+  }
+
+  let removed: f64 = segments.iter().filter(|segment| !segment.kept).map(|segment| segment.length()).sum();
+  let html = format!(
+    "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>LectureCut review: {title}</title>
+<style>{style}</style>
+</head>
+<body>
+<h1>{title}</h1>
+<p>Output: {output}<br>Kept {kept} of {duration}, removed {removed} in {gaps} gaps.</p>
+<video id=\"video\" src=\"{src}\" controls preload=\"metadata\"></video>
+<div id=\"timeline\">
+{timeline}<div id=\"playhead\"></div>
+</div>
+<table>
+<tr><th>Part</th><th>Start</th><th>End</th><th>Duration</th><th></th></tr>
+{rows}</table>
+<script>const DURATION = {duration_seconds};{script}</script>
+</body>
+</html>
+",
+    title = escape_html(input),
+    style = STYLE,
+    output = escape_html(output),
+    kept = format_timestamp(duration - removed),
+    duration = format_timestamp(duration),
+    removed = format_timestamp(removed),
+    gaps = segments.iter().filter(|segment| !segment.kept).count(),
+    src = escape_html(file_url(Path::new(input)).as_str()),
+    timeline = timeline,
+    rows = rows,
+    duration_seconds = duration,
+    script = SCRIPT,
+  );
+  // never replaces a file, even if one appeared since the arguments were checked
+  OpenOptions::new().write(true).create_new(true).open(path)?.write_all(html.as_bytes())
+}
@@ -0,0 +1,292 @@
+extern crate tiny_http;
+extern crate serde;
+extern crate serde_json;
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Mutex;
+
+use self::serde::{Deserialize, Serialize};
+use self::tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::argmunents::Options;
+use crate::helper::get_automatic_path;
+use crate::module_manager::{ArgumentList, ArgumentResult, Cut, GeneratorStats};
+use crate::printer::raise_error;
+
+#[derive(Deserialize)]
+struct JobRequest {
+  input: String,
+  #[serde(default)]
+  output: Option<String>,
+  #[serde(default)]
+  tsonly: Option<bool>,
+  #[serde(default)]
+  generator_args: BTreeMap<String, String>,
+  #[serde(default)]
+  render_args: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum JobStatus {
+  Queued,
+  Running,
+  Done,
+  Failed,
+}
+
+struct Job {
+  options: Options,
+  status: JobStatus,
+  error: Option<String>,
+  progress: HashMap<String, f64>,
+  stats: Option<GeneratorStats>,
+  cuts: Option<Vec<Cut>>,
+}
+
+#[derive(Serialize)]
+struct JobSummary<'a> {
+  id: usize,
+  input: &'a str,
+  output: &'a str,
+  status: JobStatus,
+  progress: HashMap<String, f64>,
+  stats: &'a Option<GeneratorStats>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  error: &'a Option<String>,
+}
+
+#[derive(Serialize)]
+struct ErrorResponse<'a> {
+  error: &'a str,
+}
+
+fn job_summary<'a>(id: usize, job: &'a Job, progress: &dyn Fn() -> HashMap<String, f64>) -> JobSummary<'a> {
+  JobSummary {
+    id,
+    input: job.options.input.as_str(),
+    output: job.options.output.as_str(),
+    status: job.status,
+    progress: if job.status == JobStatus::Running { progress() } else { job.progress.clone() },
+    stats: &job.stats,
+    error: &job.error,
+  }
+}
+
+fn respond_json<T: Serialize>(request: Request, status: u16, value: &T) {
+  let body = serde_json::to_string(value).unwrap_or_else(|_| "{}".to_string());
+  let mut response = Response::from_string(body).with_status_code(status);
+  if let Ok(header) = Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]) {
+    response = response.with_header(header);
+  }
+  if let Err(e) = request.respond(response) {
+    eprintln!("Error: {}", e);
+  }
+}
+
+fn respond_error(request: Request, status: u16, message: &str) {
+  respond_json(request, status, &ErrorResponse { error: message });
+}
+
+/// Maps the arguments of a job request onto the arguments a module understands.
+fn map_job_args(requested: &BTreeMap<String, String>, known: &ArgumentList, kind: &str) -> Result<Vec<ArgumentResult>, String> {
+  for long in requested.keys() {
+    if !known.iter().any(|arg| &arg.long == long) {
+      return Err(format!("Unknown {} argument: {}", kind, long));
+    }
+  }
+  let mut results = Vec::new();
+  for arg in known {
+    match requested.get(&arg.long) {
+      Some(value) => results.push(ArgumentResult {
+        long: arg.long.clone(),
+        value: value.clone(),
+      }),
+      None if arg.required => return Err(format!("Missing required {} argument: {}", kind, arg.long)),
+      None => {},
+    }
+  }
+  Ok(results)
+}
+
+/// What the jobs are built from: the options and module arguments given on the command line.
+struct JobDefaults<'a> {
+  options: &'a Options,
+  generator_args: &'a ArgumentList<'a>,
+  render_args: &'a ArgumentList<'a>,
+  /// Completes the options of a submitted job, or rejects it with an error.
+  prepare: &'a (dyn Fn(&mut Options) -> Result<(), String> + Sync),
+}
+
+fn create_job(request: &mut Request, defaults: &JobDefaults) -> Result<Job, String> {
+  let options = defaults.options;
+  let job_request: JobRequest = serde_json::from_reader(request.as_reader()).map_err(|e| format!("Invalid job: {}", e))?;
+
+  if !Path::new(&job_request.input).is_file() {
+    return Err("Input file does not exist.".to_string());
+  }
+  let tsonly = job_request.tsonly.unwrap_or(options.tsonly);
+  let output = match job_request.output {
+    Some(output) => output,
+    None => get_automatic_path(job_request.input.as_str(), None, &Options { tsonly, ..options.clone() }),
+  };
+  if Path::new(&output).exists() {
+    return Err("Output file already exists.".to_string());
+  }
+
+  let mut options = Options {
+    input: job_request.input,
+    output,
+    tsonly,
+    generator_args: map_job_args(&job_request.generator_args, defaults.generator_args, "generator")?,
+    render_args: map_job_args(&job_request.render_args, defaults.render_args, "render")?,
+    ..options.clone()
+  };
+  (defaults.prepare)(&mut options)?;
+
+  Ok(Job {
+    options,
+    status: JobStatus::Queued,
+    error: None,
+    progress: HashMap::new(),
+    stats: None,
+    cuts: None,
+  })
+}
+
+fn handle_request(mut request: Request, jobs: &Mutex<Vec<Job>>, tx: &Sender<usize>, defaults: &JobDefaults, progress: &dyn Fn() -> HashMap<String, f64>) {
+  let url = request.url().trim_end_matches('/').to_string();
+  let parts: Vec<&str> = url.split('/').skip(1).collect();
+
+  // the body is read before the job list is locked, so the worker can carry on in the meantime
+  let created = match (request.method(), parts.as_slice()) {
+    (Method::Post, ["jobs"]) => Some(create_job(&mut request, defaults)),
+    _ => None,
+  };
+
+  let mut locked_jobs = match jobs.lock() {
+    Ok(locked_jobs) => locked_jobs,
+    Err(_) => {
+      respond_error(request, 500, "Job list is unavailable.");
+      return;
+    }
+  };
+
+  let summary = |id: usize, job| job_summary(id, job, progress);
+
+  match (request.method(), parts.as_slice(), created) {
+    (_, _, Some(created)) => {
+      match created {
+        Ok(job) if locked_jobs.iter().any(|other| other.options.output == job.options.output) => {
+          respond_error(request, 409, "Another job already writes to this output file.");
+        },
+        Ok(job) => {
+          let id = locked_jobs.len();
+          locked_jobs.push(job);
+          if tx.send(id).is_err() {
+            respond_error(request, 500, "Worker is not running.");
+            return;
+          }
+          respond_json(request, 201, &summary(id, &locked_jobs[id]));
+        },
+        Err(e) => respond_error(request, 400, e.as_str()),
+      }
+    },
+    (Method::Get, ["jobs"], _) => {
+      let summaries: Vec<JobSummary> = locked_jobs.iter().enumerate().map(|(id, job)| summary(id, job)).collect();
+      respond_json(request, 200, &summaries);
+    },
+    (Method::Get, ["jobs", id, rest @ ..], _) => {
+      let job = match id.parse::<usize>().ok().and_then(|id| locked_jobs.get(id).map(|job| (id, job))) {
+        Some(job) => job,
+        None => {
+          respond_error(request, 404, "Job not found.");
+          return;
+        }
+      };
+      match (rest, &job.1.cuts, &job.1.stats) {
+        ([], _, _) => respond_json(request, 200, &summary(job.0, job.1)),
+        (["cuts"], Some(cuts), _) => respond_json(request, 200, cuts),
+        (["stats"], _, Some(stats)) => respond_json(request, 200, stats),
+        (["cuts"], _, _) | (["stats"], _, _) if job.1.status == JobStatus::Failed => respond_error(request, 409, "Job failed."),
+        (["cuts"], _, _) | (["stats"], _, _) => respond_error(request, 409, "Job is not finished yet."),
+        _ => respond_error(request, 404, "Not found."),
+      }
+    },
+    _ => respond_error(request, 404, "Not found."),
+  }
+}
+
+/// Serves the job API on `bind` until the process is terminated.
+///
+/// `POST /jobs` queues a job, `GET /jobs` lists all jobs, `GET /jobs/<id>` reports status and progress,
+/// `GET /jobs/<id>/cuts` and `GET /jobs/<id>/stats` return the results of a finished job.
+/// `prepare` completes the options of a submitted job, a job it returns an error for is rejected.
+/// Jobs are processed one after another by `process`, `progress` reports the progress of the running job.
+/// A job `process` returns an error for is marked as failed, and the server carries on with the next one.
+pub fn serve<C, P, R>(bind: &str, options: &Options, generator_args: &ArgumentList, render_args: &ArgumentList, prepare: C, process: P, progress: R)
+where
+  C: Fn(&mut Options) -> Result<(), String> + Sync,
+  P: Fn(&Options) -> Result<(Vec<Cut>, GeneratorStats), String> + Sync,
+  R: Fn() -> HashMap<String, f64> + Sync,
+{
+  let server = match Server::http(bind) {
+    Ok(server) => server,
+    Err(e) => {
+      raise_error(format!("Could not listen on {}: {}", bind, e).as_str());
+      return;
+    }
+  };
+
+  let jobs: Mutex<Vec<Job>> = Mutex::new(Vec::new());
+  let defaults = JobDefaults { options, generator_args, render_args, prepare: &prepare };
+  let (tx, rx) = channel::<usize>();
+
+  std::thread::scope(|scope| {
+    let jobs = &jobs;
+    let defaults = &defaults;
+    let process = &process;
+    let progress = &progress;
+
+    // worker processing one job after another
+    scope.spawn(move || {
+      for id in rx {
+        let options = if let Ok(mut locked_jobs) = jobs.lock() {
+          locked_jobs[id].status = JobStatus::Running;
+          locked_jobs[id].options.clone()
+        } else {
+          continue;
+        };
+
+        let result = process(&options);
+
+        if let Ok(mut locked_jobs) = jobs.lock() {
+          let job = &mut locked_jobs[id];
+          job.progress = progress();
+          match result {
+            Ok((cuts, stats)) => {
+              job.status = JobStatus::Done;
+              job.stats = Some(stats);
+              job.cuts = Some(cuts);
+            },
+            Err(e) => {
+              job.status = JobStatus::Failed;
+              job.error = Some(e);
+            },
+          }
+        }
+      }
+    });
+
+    for request in server.incoming_requests() {
+      let tx = tx.clone();
+      // every request is answered on its own thread, so a slow client does not hold up the others
+      scope.spawn(move || handle_request(request, jobs, &tx, defaults, progress));
+    }
+
+    // stop the worker once the server shuts down
+    drop(tx);
+  });
+}
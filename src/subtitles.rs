@@ -0,0 +1,229 @@
+use std::path::{Path, PathBuf};
+
+use crate::module_manager::Cut;
+use crate::timeline::{format_timestamp, parse_timestamp};
+
+#[derive(Clone, Copy, PartialEq)]
+enum SubtitleFormat {
+  Srt,
+  WebVtt,
+}
+
+struct Cue {
+  id: Option<String>,
+  start: f64,
+  end: f64,
+  /// WebVTT cue settings following the timing, e.g. "align:start".
+  settings: String,
+  text: String,
+}
+
+struct Subtitles {
+  format: SubtitleFormat,
+  /// WebVTT header and style blocks, kept as they are.
+  header: Vec<String>,
+  cues: Vec<Cue>,
+}
+
+fn format_subtitle_timestamp(seconds: f64, format: SubtitleFormat) -> String {
+  match format {
+    SubtitleFormat::Srt => format_timestamp(seconds).replace('.', ","),
+    SubtitleFormat::WebVtt => format_timestamp(seconds),
+  }
+}
+
+fn parse_cue(block: &str) -> Option<Cue> {
+  let lines: Vec<&str> = block.lines().collect();
+  let timing_line = lines.iter().position(|line| line.contains("-->"))?;
+  let (start, rest) = lines[timing_line].split_once("-->")?;
+  let rest = rest.trim();
+  let (end, settings) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+  Some(Cue {
+    id: if timing_line > 0 { Some(lines[..timing_line].join("\n")) } else { None },
+    start: parse_timestamp(start.trim().replace(',', ".").as_str())?,
+    end: parse_timestamp(end.replace(',', ".").as_str())?,
+    settings: settings.trim().to_string(),
+    text: lines[timing_line + 1..].join("\n"),
+  })
+}
+
+fn parse(content: &str, format: SubtitleFormat) -> Result<Subtitles, String> {
+  let content = content.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+  let mut subtitles = Subtitles {
+    format,
+    header: Vec::new(),
+    cues: Vec::new(),
+  };
+  for block in content.split("\n\n").map(|block| block.trim_matches('\n')).filter(|block| !block.is_empty()) {
+    if block.lines().any(|line| line.contains("-->")) {
+      match parse_cue(block) {
+        Some(cue) => subtitles.cues.push(cue),
+        None => return Err(format!("Invalid cue \"{}\".", block.lines().next().unwrap_or_default())),
+      }
+    } else if format == SubtitleFormat::WebVtt && subtitles.cues.is_empty() && !block.starts_with("NOTE") {
+      subtitles.header.push(block.to_string());
+    }
+  }
+  if format == SubtitleFormat::WebVtt && !subtitles.header.first().is_some_and(|header| header.starts_with("WEBVTT")) {
+    return Err("WebVTT files need to start with WEBVTT.".to_string());
+  }
+  Ok(subtitles)
+}
+
+/// Moves every cue to the output timeline.
+/// Cues entirely inside removed parts are dropped, cues spanning removed parts are split at them.
+fn retime(subtitles: Subtitles, cuts: &[Cut]) -> Subtitles {
+  let mut cues = Vec::new();
+  for cue in subtitles.cues {
+    let mut output_time = 0.0;
+    let mut first = true;
+    for cut in cuts {
+      let start = cue.start.max(cut.start);
+      let end = cue.end.min(cut.end);
+      if start < end {
+        cues.push(Cue {
+          // identifiers have to be unique
+          id: if first { cue.id.clone() } else { None },
+          start: output_time + start - cut.start,
+          end: output_time + end - cut.start,
+          settings: cue.settings.clone(),
+          text: cue.text.clone(),
+        });
+        first = false;
+      }
+      output_time += cut.end - cut.start;
+    }
+  }
+  Subtitles { cues, ..subtitles }
+}
+
+fn write(subtitles: &Subtitles) -> String {
+  let mut blocks = subtitles.header.clone();
+  for (i, cue) in subtitles.cues.iter().enumerate() {
+    let start = format_subtitle_timestamp(cue.start, subtitles.format);
+    let end = format_subtitle_timestamp(cue.end, subtitles.format);
+    let mut block = match (subtitles.format, &cue.id) {
+      // SRT cues are numbered, so they are renumbered after dropping and splitting
+      (SubtitleFormat::Srt, _) => format!("{}\n", i + 1),
+      (SubtitleFormat::WebVtt, Some(id)) => format!("{}\n", id),
+      (SubtitleFormat::WebVtt, None) => String::new(),
+    };
+    block += format!("{} --> {}", start, end).as_str();
+    if !cue.settings.is_empty() {
+      block += format!(" {}", cue.settings).as_str();
+    }
+    block += format!("\n{}", cue.text).as_str();
+    blocks.push(block);
+  }
+  blocks.join("\n\n") + "\n"
+}
+
+fn subtitle_format(path: &Path) -> Option<SubtitleFormat> {
+  match path.extension()?.to_str()?.to_lowercase().as_str() {
+    "srt" => Some(SubtitleFormat::Srt),
+    "vtt" => Some(SubtitleFormat::WebVtt),
+    _ => None,
+  }
+}
+
+pub fn is_subtitle_file(path: &Path) -> bool {
+  subtitle_format(path).is_some()
+}
+
+/// Path the subtitles at `input` are written to when retimed for `output`.
+pub fn retimed_path(input: &Path, output: &Path) -> PathBuf {
+  output.with_extension(input.extension().unwrap_or_default())
+}
+
+/// Checks that retiming the subtitles at `input` for `output` does not overwrite anything.
+pub fn check_retimed_path(input: &Path, output: &Path) -> Result<(), String> {
+  let target = retimed_path(input, output);
+  if !target.exists() {
+    return Ok(());
+  }
+  if input.canonicalize().ok() == target.canonicalize().ok() {
+    return Err(format!("The retimed subtitles would overwrite the original subtitles {}.", input.display()));
+  }
+  Err(format!("Subtitle file {} already exists.", target.display()))
+}
+
+/// Retimes the subtitles at `input` to the cut video and writes them beside `output`, in the same format.
+pub fn retime_file(input: &Path, output: &Path, cuts: &[Cut]) -> Result<(), String> {
+  check_retimed_path(input, output)?;
+  let format = subtitle_format(input).ok_or("Subtitles need to be SRT or WebVTT files.")?;
+  let content = std::fs::read_to_string(input).map_err(|e| e.to_string())?;
+  let subtitles = retime(parse(content.as_str(), format)?, cuts);
+  std::fs::write(retimed_path(input, output), write(&subtitles)).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn kept() -> Vec<Cut> {
+    vec![Cut { start: 1.0, end: 4.0 }, Cut { start: 6.0, end: 9.5 }, Cut { start: 12.0, end: 20.0 }]
+  }
+
+  fn retimed(content: &str, format: SubtitleFormat) -> String {
+    write(&retime(parse(content, format).unwrap(), &kept()))
+  }
+
+  #[test]
+  fn srt_cues_are_parsed() {
+    let subtitles = parse("\u{feff}1\r\n00:00:01,500 --> 00:00:03,000\r\nHello\r\nworld\r\n\r\n2\r\n00:00:06,000 --> 00:00:07,250\r\nAgain\r\n", SubtitleFormat::Srt).unwrap();
+    assert_eq!(subtitles.cues.len(), 2);
+    assert_eq!(subtitles.cues[0].id.as_deref(), Some("1"));
+    assert_eq!((subtitles.cues[0].start, subtitles.cues[0].end), (1.5, 3.0));
+    assert_eq!(subtitles.cues[0].text, "Hello\nworld");
+    assert_eq!((subtitles.cues[1].start, subtitles.cues[1].end), (6.0, 7.25));
+  }
+
+  #[test]
+  fn webvtt_keeps_header_and_settings() {
+    let subtitles = parse("WEBVTT\n\nSTYLE\n::cue { color: red }\n\nNOTE skipped\n\n00:01.000 --> 00:02.000 align:start\nHi\n", SubtitleFormat::WebVtt).unwrap();
+    assert_eq!(subtitles.header, ["WEBVTT", "STYLE\n::cue { color: red }"]);
+    assert_eq!(subtitles.cues.len(), 1);
+    assert!(subtitles.cues[0].id.is_none());
+    assert_eq!(subtitles.cues[0].settings, "align:start");
+  }
+
+  #[test]
+  fn malformed_subtitles() {
+    assert!(parse("1\n00:00:xx,000 --> 00:00:02,000\nHi\n", SubtitleFormat::Srt).is_err());
+    assert!(parse("1\n00:00:01,000 --> \nHi\n", SubtitleFormat::Srt).is_err());
+    assert!(parse("1\n00:00:01,000 --> -00:00:02,000\nHi\n", SubtitleFormat::Srt).is_err());
+    assert!(parse("00:01.000 --> 00:02.000\nHi\n", SubtitleFormat::WebVtt).is_err());
+  }
+
+  #[test]
+  fn cues_move_to_the_output_timeline() {
+    assert_eq!(
+      retimed("1\n00:00:02,000 --> 00:00:03,500\nA\n\n2\n00:00:12,500 --> 00:00:13,000\nB\n", SubtitleFormat::Srt),
+      "1\n00:00:01,000 --> 00:00:02,500\nA\n\n2\n00:00:07,000 --> 00:00:07,500\nB\n",
+    );
+  }
+
+  #[test]
+  fn cues_inside_removed_parts_are_dropped() {
+    // the second cue lies entirely in the gap from 4 to 6, the third after the last kept part
+    assert_eq!(
+      retimed("1\n00:00:02,000 --> 00:00:03,000\nA\n\n2\n00:00:04,500 --> 00:00:05,500\nB\n\n3\n00:00:21,000 --> 00:00:22,000\nC\n", SubtitleFormat::Srt),
+      "1\n00:00:01,000 --> 00:00:02,000\nA\n",
+    );
+    // touching a kept part is not overlapping it
+    assert_eq!(retimed("1\n00:00:04,000 --> 00:00:06,000\nB\n", SubtitleFormat::Srt), "\n");
+  }
+
+  #[test]
+  fn cues_spanning_removed_parts_are_split() {
+    assert_eq!(
+      retimed("1\n00:00:03,000 --> 00:00:07,000\nA\n\n2\n00:00:09,000 --> 00:00:10,000\nB\n", SubtitleFormat::Srt),
+      "1\n00:00:02,000 --> 00:00:03,000\nA\n\n2\n00:00:03,000 --> 00:00:04,000\nA\n\n3\n00:00:06,000 --> 00:00:06,500\nB\n",
+    );
+    // WebVTT identifiers stay with the first part only
+    assert_eq!(
+      retimed("WEBVTT\n\nintro\n00:03.000 --> 00:07.000 line:0\nA\n", SubtitleFormat::WebVtt),
+      "WEBVTT\n\nintro\n00:00:02.000 --> 00:00:03.000 line:0\nA\n\n00:00:03.000 --> 00:00:04.000 line:0\nA\n",
+    );
+  }
+}
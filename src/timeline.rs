@@ -0,0 +1,512 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use crate::module_manager::Cut;
+
+/// A chapter of the output, in output time.
+pub struct Chapter {
+  pub start: f64,
+  pub end: f64,
+}
+
+/// Formats seconds as HH:MM:SS.mmm, as used by WebVTT.
+pub fn format_timestamp(seconds: f64) -> String {
+  let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+  format!("{:02}:{:02}:{:02}.{:03}", millis / 3600000, millis / 60000 % 60, millis / 1000 % 60, millis % 1000)
+}
+
+/// A kept or removed part of the input.
+pub struct Segment {
+  pub start: f64,
+  pub end: f64,
+  pub kept: bool,
+}
+
+impl Segment {
+  pub fn length(&self) -> f64 {
+    self.end - self.start
+  }
+}
+
+/// Splits the input of `duration` seconds into alternating kept and removed segments.
+pub fn segments(cuts: &[Cut], duration: f64) -> Vec<Segment> {
+  let mut segments = Vec::new();
+  let mut position = 0.0;
+  for cut in cuts {
+    if cut.start > position {
+      segments.push(Segment { start: position, end: cut.start, kept: false });
+    }
+    segments.push(Segment { start: cut.start, end: cut.end, kept: true });
+    position = cut.end;
+  }
+  if duration > position {
+    segments.push(Segment { start: position, end: duration, kept: false });
+  }
+  segments
+}
+
+/// Applies the edits of a review: the `restored` parts are kept again and every kept part
+/// is padded by `padding` seconds on both sides. Overlapping parts are merged.
+pub fn edit_cuts(cuts: &[Cut], restored: &[Cut], padding: f64, duration: f64) -> Vec<Cut> {
+  let mut kept: Vec<Cut> = cuts.iter().chain(restored).map(|cut| Cut {
+    start: (cut.start - padding).max(0.0),
+    end: (cut.end + padding).min(duration),
+  }).collect();
+  kept.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+  let mut merged: Vec<Cut> = Vec::new();
+  for cut in kept {
+    match merged.last_mut() {
+      Some(last) if cut.start <= last.end => last.end = last.end.max(cut.end),
+      _ => merged.push(cut),
+    }
+  }
+  merged
+}
+
+/// Removes everything before `start` and after `end` seconds from the kept segments.
+pub fn trim(cuts: &[Cut], start: f64, end: f64) -> Vec<Cut> {
+  cuts.iter()
+    .map(|cut| Cut { start: cut.start.max(start), end: cut.end.min(end) })
+    .filter(|cut| cut.end > cut.start)
+    .collect()
+}
+
+/// Parses timestamps like 12:34, 1:02:03.5 or 754.5 into seconds.
+pub fn parse_timestamp(text: &str) -> Option<f64> {
+  let mut seconds = 0.0;
+  for part in text.trim().split(':') {
+    // only plain digits, so signs, "inf" and "nan" are rejected
+    if !part.chars().all(|c| c.is_ascii_digit() || c == '.') {
+      return None;
+    }
+    seconds = seconds * 60.0 + part.parse::<f64>().ok()?;
+  }
+  Some(seconds)
+}
+
+/// Maps a timestamp of the input to the output.
+/// Timestamps in removed parts map to the point the output continues at and are reported as not kept.
+pub fn input_to_output(cuts: &[Cut], time: f64) -> (f64, bool) {
+  let mut output_time = 0.0;
+  for cut in cuts {
+    if time < cut.start {
+      return (output_time, false);
+    }
+    if time <= cut.end {
+      return (output_time + time - cut.start, true);
+    }
+    output_time += cut.end - cut.start;
+  }
+  (output_time, false)
+}
+
+/// Maps a timestamp of the output back to the input. Returns None if it lies beyond the end of the output.
+pub fn output_to_input(cuts: &[Cut], time: f64) -> Option<f64> {
+  let mut output_time = 0.0;
+  for cut in cuts {
+    let length = cut.end - cut.start;
+    if time <= output_time + length {
+      return Some(cut.start + (time - output_time).max(0.0));
+    }
+    output_time += length;
+  }
+  None
+}
+
+/// Writes the timestamp map: one line per kept segment with its input start and end and its output start.
+pub fn write_map(path: &Path, cuts: &[Cut]) -> std::io::Result<()> {
+  let mut writer = BufWriter::new(File::create(path)?);
+  writeln!(writer, "input_start,input_end,output_start")?;
+  let mut output_time = 0.0;
+  for cut in cuts {
+    writeln!(writer, "{},{},{}", cut.start, cut.end, output_time)?;
+    output_time += cut.end - cut.start;
+  }
+  writer.flush()
+}
+
+/// Reads kept segments from a CSV file whose first two columns are start and end,
+/// like `--tsonly` output or a timestamp map. A header line is skipped.
+pub fn read_cuts(path: &Path) -> Result<Vec<Cut>, String> {
+  let file = File::open(path).map_err(|e| e.to_string())?;
+  let mut cuts = Vec::new();
+  for (i, line) in BufReader::new(file).lines().enumerate() {
+    let line = line.map_err(|e| e.to_string())?;
+    if line.trim().is_empty() {
+      continue;
+    }
+    let mut values = line.split(',').map(|value| value.trim().parse::<f64>());
+    match (values.next(), values.next()) {
+      (Some(Ok(start)), Some(Ok(end))) => cuts.push(Cut { start, end }),
+      _ if i == 0 => {},
+      _ => return Err(format!("Line {} of {} is invalid.", i + 1, path.display())),
+    }
+  }
+  Ok(cuts)
+}
+
+/// Seconds kept in both cut lists.
+pub fn intersection_length(a: &[Cut], b: &[Cut]) -> f64 {
+  let mut length = 0.0;
+  for cut_a in a {
+    for cut_b in b {
+      length += (cut_a.end.min(cut_b.end) - cut_a.start.max(cut_b.start)).max(0.0);
+    }
+  }
+  length
+}
+
+pub fn kept_length(cuts: &[Cut]) -> f64 {
+  cuts.iter().map(|cut| cut.end - cut.start).sum()
+}
+
+/// How well generated cuts match hand-labelled speech regions.
+pub struct Evaluation {
+  pub precision: f64,
+  pub recall: f64,
+  pub f1: f64,
+  /// Seconds of speech that were removed.
+  pub false_removal: f64,
+  /// Seconds without speech that were kept.
+  pub false_keep: f64,
+  /// Mean and maximum distance in seconds of every labelled boundary to the nearest generated one.
+  pub boundary_error: Option<(f64, f64)>,
+}
+
+/// Compares the kept segments with the labelled speech regions in `truth`.
+pub fn evaluate(cuts: &[Cut], truth: &[Cut]) -> Evaluation {
+  // labels may overlap or be unsorted
+  let truth = edit_cuts(truth, &[], 0.0, f64::INFINITY);
+  let kept = kept_length(cuts);
+  let speech = kept_length(&truth);
+  let true_positive = intersection_length(cuts, &truth);
+
+  let precision = if kept > 0.0 { true_positive / kept } else { 0.0 };
+  let recall = if speech > 0.0 { true_positive / speech } else { 0.0 };
+  let f1 = if precision + recall > 0.0 { 2.0 * precision * recall / (precision + recall) } else { 0.0 };
+
+  let starts: Vec<f64> = cuts.iter().map(|cut| cut.start).collect();
+  let ends: Vec<f64> = cuts.iter().map(|cut| cut.end).collect();
+  let nearest = |time: f64, boundaries: &[f64]| boundaries.iter().map(|boundary| (boundary - time).abs()).fold(f64::INFINITY, f64::min);
+  let errors: Vec<f64> = truth.iter().flat_map(|label| [
+    nearest(label.start, &starts),
+    nearest(label.end, &ends),
+  ]).filter(|error| error.is_finite()).collect();
+  let boundary_error = if errors.is_empty() {
+    None
+  } else {
+    Some((errors.iter().sum::<f64>() / errors.len() as f64, errors.iter().copied().fold(0.0, f64::max)))
+  };
+
+  Evaluation {
+    precision,
+    recall,
+    f1,
+    false_removal: speech - true_positive,
+    false_keep: kept - true_positive,
+    boundary_error,
+  }
+}
+
+/// Overlapping kept segments of two cut lists.
+pub struct CutChange {
+  pub before: Vec<Cut>,
+  pub after: Vec<Cut>,
+}
+
+impl CutChange {
+  pub fn is_unchanged(&self) -> bool {
+    match (self.before.as_slice(), self.after.as_slice()) {
+      ([before], [after]) => (before.start - after.start).abs() < 0.001 && (before.end - after.end).abs() < 0.001,
+      _ => false,
+    }
+  }
+}
+
+/// Groups the kept segments of `before` and `after` into clusters of overlapping segments.
+/// A cluster without segments before was added, one without segments after was removed.
+pub fn diff_cuts(before: &[Cut], after: &[Cut]) -> Vec<CutChange> {
+  let mut all: Vec<(Cut, bool)> = before.iter().map(|cut| (*cut, false)).chain(after.iter().map(|cut| (*cut, true))).collect();
+  all.sort_by(|a, b| a.0.start.total_cmp(&b.0.start));
+
+  let mut changes: Vec<CutChange> = Vec::new();
+  let mut end = f64::NEG_INFINITY;
+  for (cut, is_after) in all {
+    if changes.is_empty() || cut.start >= end {
+      changes.push(CutChange { before: Vec::new(), after: Vec::new() });
+    }
+    if let Some(change) = changes.last_mut() {
+      if is_after { change.after.push(cut) } else { change.before.push(cut) }
+    }
+    end = end.max(cut.end);
+  }
+  changes
+}
+
+/// Splits the output into chapters, starting a new one after every removed gap longer than `min_gap` seconds.
+pub fn chapters(cuts: &[Cut], min_gap: f64) -> Vec<Chapter> {
+  let mut chapters: Vec<Chapter> = Vec::new();
+  let mut output_time = 0.0;
+  let mut previous_end: Option<f64> = None;
+  for cut in cuts {
+    let length = cut.end - cut.start;
+    match (chapters.last_mut(), previous_end) {
+      (Some(chapter), Some(previous_end)) if cut.start - previous_end <= min_gap => chapter.end += length,
+      _ => chapters.push(Chapter { start: output_time, end: output_time + length }),
+    }
+    output_time += length;
+    previous_end = Some(cut.end);
+  }
+  chapters
+}
+
+pub fn write_webvtt_chapters(path: &Path, chapters: &[Chapter]) -> std::io::Result<()> {
+  let mut writer = BufWriter::new(File::create(path)?);
+  writeln!(writer, "WEBVTT")?;
+  for (i, chapter) in chapters.iter().enumerate() {
+    writeln!(writer)?;
+    writeln!(writer, "{}", i + 1)?;
+    writeln!(writer, "{} --> {}", format_timestamp(chapter.start), format_timestamp(chapter.end))?;
+    writeln!(writer, "Chapter {}", i + 1)?;
+  }
+  writer.flush()
+}
+
+/// Writes the chapters in ffmpeg's metadata format, ready for `ffmpeg -i output -i chapters -map_chapters 1`.
+pub fn write_ffmetadata_chapters(path: &Path, chapters: &[Chapter]) -> std::io::Result<()> {
+  let mut writer = BufWriter::new(File::create(path)?);
+  writeln!(writer, ";FFMETADATA1")?;
+  for (i, chapter) in chapters.iter().enumerate() {
+    writeln!(writer, "[CHAPTER]")?;
+    writeln!(writer, "TIMEBASE=1/1000")?;
+    writeln!(writer, "START={}", (chapter.start * 1000.0).round() as u64)?;
+    writeln!(writer, "END={}", (chapter.end * 1000.0).round() as u64)?;
+    writeln!(writer, "title=Chapter {}", i + 1)?;
+  }
+  writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn cuts(spans: &[(f64, f64)]) -> Vec<Cut> {
+    spans.iter().map(|&(start, end)| Cut { start, end }).collect()
+  }
+
+  fn spans(cuts: &[Cut]) -> Vec<(f64, f64)> {
+    cuts.iter().map(|cut| (cut.start, cut.end)).collect()
+  }
+
+  #[test]
+  fn padding_stays_within_the_input() {
+    let edited = edit_cuts(&cuts(&[(0.5, 4.0), (10.0, 19.5)]), &[], 1.0, 20.0);
+    assert_eq!(spans(&edited), [(0.0, 5.0), (9.0, 20.0)]);
+  }
+
+  #[test]
+  fn padding_merges_close_segments() {
+    let edited = edit_cuts(&cuts(&[(1.0, 4.0), (5.0, 8.0), (12.0, 14.0)]), &[], 0.5, 20.0);
+    assert_eq!(spans(&edited), [(0.5, 8.5), (11.5, 14.5)]);
+  }
+
+  #[test]
+  fn restored_gaps_merge_with_their_neighbours() {
+    let kept = cuts(&[(1.0, 4.0), (6.0, 9.5), (12.0, 20.0)]);
+    let edited = edit_cuts(&kept, &cuts(&[(4.0, 6.0)]), 0.0, 30.0);
+    assert_eq!(spans(&edited), [(1.0, 9.5), (12.0, 20.0)]);
+
+    let edited = edit_cuts(&kept, &cuts(&[(20.0, 30.0), (9.5, 12.0)]), 0.0, 30.0);
+    assert_eq!(spans(&edited), [(1.0, 4.0), (6.0, 30.0)]);
+  }
+
+  #[test]
+  fn no_edits_keep_the_cuts() {
+    let kept = cuts(&[(1.0, 4.0), (6.0, 9.5)]);
+    assert_eq!(spans(&edit_cuts(&kept, &[], 0.0, 30.0)), spans(&kept));
+    assert!(edit_cuts(&[], &[], 1.0, 30.0).is_empty());
+  }
+
+  #[test]
+  fn touching_segments_are_separate_changes() {
+    let changes = diff_cuts(&cuts(&[(0.0, 5.0)]), &cuts(&[(5.0, 8.0)]));
+    assert_eq!(changes.len(), 2);
+    assert_eq!(spans(&changes[0].before), [(0.0, 5.0)]);
+    assert!(changes[0].after.is_empty());
+    assert!(changes[1].before.is_empty());
+    assert_eq!(spans(&changes[1].after), [(5.0, 8.0)]);
+  }
+
+  #[test]
+  fn overlapping_segments_form_one_change() {
+    let changes = diff_cuts(&cuts(&[(0.0, 5.0), (6.0, 9.0)]), &cuts(&[(4.0, 7.0), (20.0, 21.0)]));
+    assert_eq!(changes.len(), 2);
+    assert_eq!(spans(&changes[0].before), [(0.0, 5.0), (6.0, 9.0)]);
+    assert_eq!(spans(&changes[0].after), [(4.0, 7.0)]);
+    assert!(changes[1].before.is_empty());
+    assert_eq!(spans(&changes[1].after), [(20.0, 21.0)]);
+  }
+
+  #[test]
+  fn diff_of_empty_lists() {
+    assert!(diff_cuts(&[], &[]).is_empty());
+
+    let changes = diff_cuts(&cuts(&[(1.0, 2.0)]), &[]);
+    assert_eq!(changes.len(), 1);
+    assert_eq!(spans(&changes[0].before), [(1.0, 2.0)]);
+    assert!(changes[0].after.is_empty());
+
+    let changes = diff_cuts(&[], &cuts(&[(1.0, 2.0), (3.0, 4.0)]));
+    assert_eq!(changes.len(), 2);
+    assert!(changes.iter().all(|change| change.before.is_empty() && change.after.len() == 1));
+  }
+
+  #[test]
+  fn unchanged_only_with_one_matching_segment_on_each_side() {
+    let change = |before: &[(f64, f64)], after: &[(f64, f64)]| CutChange { before: cuts(before), after: cuts(after) };
+    assert!(change(&[(1.0, 2.0)], &[(1.0, 2.0)]).is_unchanged());
+    assert!(change(&[(1.0, 2.0)], &[(1.0002, 1.9995)]).is_unchanged());
+    assert!(!change(&[(1.0, 2.0)], &[(1.0, 2.1)]).is_unchanged());
+    assert!(!change(&[(1.0, 2.0)], &[]).is_unchanged());
+    assert!(!change(&[], &[(1.0, 2.0)]).is_unchanged());
+    assert!(!change(&[(1.0, 2.0)], &[(1.0, 1.5), (1.5, 2.0)]).is_unchanged());
+  }
+
+  #[test]
+  fn intersection_of_cut_lists() {
+    let a = cuts(&[(0.0, 5.0), (10.0, 20.0)]);
+    assert_eq!(intersection_length(&a, &a), 15.0);
+    assert_eq!(intersection_length(&a, &cuts(&[(4.0, 12.0)])), 3.0);
+    assert_eq!(intersection_length(&a, &cuts(&[(5.0, 10.0)])), 0.0);
+    assert_eq!(intersection_length(&a, &[]), 0.0);
+  }
+
+  #[test]
+  fn perfect_cuts() {
+    let labels = cuts(&[(1.0, 4.0), (6.0, 9.0)]);
+    let evaluation = evaluate(&labels, &labels);
+    assert_eq!((evaluation.precision, evaluation.recall, evaluation.f1), (1.0, 1.0, 1.0));
+    assert_eq!((evaluation.false_removal, evaluation.false_keep), (0.0, 0.0));
+    assert_eq!(evaluation.boundary_error, Some((0.0, 0.0)));
+  }
+
+  #[test]
+  fn partly_matching_cuts() {
+    let evaluation = evaluate(&cuts(&[(0.0, 4.0), (8.0, 10.0)]), &cuts(&[(1.0, 4.0), (6.0, 9.0)]));
+    assert_eq!(evaluation.precision, 4.0 / 6.0);
+    assert_eq!(evaluation.recall, 4.0 / 6.0);
+    assert!((evaluation.f1 - 4.0 / 6.0).abs() < 1e-9);
+    assert_eq!(evaluation.false_removal, 2.0);
+    assert_eq!(evaluation.false_keep, 2.0);
+    // label boundaries 1, 4, 6 and 9 are 1, 0, 2 and 1 seconds off
+    assert_eq!(evaluation.boundary_error, Some((1.0, 2.0)));
+  }
+
+  #[test]
+  fn unsorted_and_overlapping_labels_count_once() {
+    let generated = cuts(&[(1.0, 4.0), (6.0, 9.0)]);
+    let labels = cuts(&[(6.0, 9.0), (1.0, 3.0), (2.0, 4.0), (7.0, 8.0)]);
+    let evaluation = evaluate(&generated, &labels);
+    assert_eq!((evaluation.precision, evaluation.recall), (1.0, 1.0));
+    assert_eq!((evaluation.false_removal, evaluation.false_keep), (0.0, 0.0));
+    assert_eq!(evaluation.boundary_error, Some((0.0, 0.0)));
+  }
+
+  #[test]
+  fn evaluation_without_cuts_or_labels() {
+    let evaluation = evaluate(&[], &cuts(&[(1.0, 4.0)]));
+    assert_eq!((evaluation.precision, evaluation.recall, evaluation.f1), (0.0, 0.0, 0.0));
+    assert_eq!(evaluation.false_removal, 3.0);
+    assert!(evaluation.boundary_error.is_none());
+
+    let evaluation = evaluate(&cuts(&[(1.0, 4.0)]), &[]);
+    assert_eq!((evaluation.precision, evaluation.recall), (0.0, 0.0));
+    assert_eq!(evaluation.false_keep, 3.0);
+    assert!(evaluation.boundary_error.is_none());
+  }
+
+  #[test]
+  fn timestamps_in_all_notations() {
+    assert_eq!(parse_timestamp("754.5"), Some(754.5));
+    assert_eq!(parse_timestamp("12:34"), Some(754.0));
+    assert_eq!(parse_timestamp(" 1:02:03.5 "), Some(3723.5));
+    assert_eq!(parse_timestamp("00:00:01.250"), Some(1.25));
+  }
+
+  #[test]
+  fn malformed_timestamps() {
+    assert_eq!(parse_timestamp(""), None);
+    assert_eq!(parse_timestamp("1:xx"), None);
+    assert_eq!(parse_timestamp("1::02"), None);
+    assert_eq!(parse_timestamp("-3"), None);
+    assert_eq!(parse_timestamp("1:-2"), None);
+    assert_eq!(parse_timestamp("-0:05"), None);
+    assert_eq!(parse_timestamp("inf"), None);
+    assert_eq!(parse_timestamp("+5"), None);
+  }
+
+  #[test]
+  fn input_times_map_to_the_output() {
+    let kept = cuts(&[(1.0, 4.0), (6.0, 9.5), (12.0, 20.0)]);
+    assert_eq!(input_to_output(&kept, 0.0), (0.0, false));
+    assert_eq!(input_to_output(&kept, 1.0), (0.0, true));
+    assert_eq!(input_to_output(&kept, 2.5), (1.5, true));
+    assert_eq!(input_to_output(&kept, 4.0), (3.0, true));
+    // removed parts map to where the output continues
+    assert_eq!(input_to_output(&kept, 5.0), (3.0, false));
+    assert_eq!(input_to_output(&kept, 6.0), (3.0, true));
+    assert_eq!(input_to_output(&kept, 25.0), (14.5, false));
+    assert_eq!(input_to_output(&[], 5.0), (0.0, false));
+  }
+
+  #[test]
+  fn output_times_map_to_the_input() {
+    let kept = cuts(&[(1.0, 4.0), (6.0, 9.5), (12.0, 20.0)]);
+    assert_eq!(output_to_input(&kept, 0.0), Some(1.0));
+    // the end of a segment is the end of the kept part, not the start of the next one
+    assert_eq!(output_to_input(&kept, 3.0), Some(4.0));
+    assert_eq!(output_to_input(&kept, 3.5), Some(6.5));
+    assert_eq!(output_to_input(&kept, 14.5), Some(20.0));
+    assert_eq!(output_to_input(&kept, 14.6), None);
+    assert_eq!(output_to_input(&[], 0.0), None);
+  }
+
+  #[test]
+  fn chapters_start_after_long_gaps() {
+    let kept = cuts(&[(1.0, 4.0), (6.0, 9.5), (12.0, 20.0)]);
+    let chapter_spans = |min_gap| -> Vec<(f64, f64)> {
+      chapters(&kept, min_gap).iter().map(|chapter| (chapter.start, chapter.end)).collect()
+    };
+    // a gap exactly as long as the minimum does not start a chapter
+    assert_eq!(chapter_spans(2.0), [(0.0, 6.5), (6.5, 14.5)]);
+    assert_eq!(chapter_spans(1.0), [(0.0, 3.0), (3.0, 6.5), (6.5, 14.5)]);
+    assert_eq!(chapter_spans(3.0), [(0.0, 14.5)]);
+    assert!(chapters(&[], 1.0).is_empty());
+  }
+
+  fn read_cuts_from(name: &str, content: &str) -> Result<Vec<(f64, f64)>, String> {
+    let path = std::env::temp_dir().join(format!("lecturecut-test-{}-{}.csv", std::process::id(), name));
+    std::fs::write(&path, content).unwrap();
+    let result = read_cuts(&path).map(|cuts| spans(&cuts));
+    std::fs::remove_file(&path).unwrap();
+    result
+  }
+
+  #[test]
+  fn cuts_are_read_with_and_without_header() {
+    assert_eq!(read_cuts_from("header", "start,end\n1,4\n\n6,9.5\n"), Ok(vec![(1.0, 4.0), (6.0, 9.5)]));
+    assert_eq!(read_cuts_from("map", "input_start,input_end,output_start\n1,4,0\n6,9.5,3\n"), Ok(vec![(1.0, 4.0), (6.0, 9.5)]));
+    assert_eq!(read_cuts_from("plain", "1, 4\n6 ,9.5"), Ok(vec![(1.0, 4.0), (6.0, 9.5)]));
+    assert_eq!(read_cuts_from("empty", ""), Ok(vec![]));
+  }
+
+  #[test]
+  fn invalid_cut_lines() {
+    let error = read_cuts_from("invalid", "start,end\n1,4\n6\n").unwrap_err();
+    assert!(error.starts_with("Line 3 of "), "{}", error);
+    assert!(read_cuts_from("second-header", "1,4\nstart,end\n").is_err());
+    assert!(read_cuts(Path::new("does-not-exist.csv")).is_err());
+  }
+}
@@ -0,0 +1,116 @@
+extern crate console;
+
+use crate::module_manager::Cut;
+use crate::timeline::{edit_cuts, format_timestamp, segments, Segment};
+
+use self::console::{style, Key, Term};
+
+const PADDING_STEP: f64 = 0.1;
+
+struct Review<'a> {
+  input: &'a str,
+  cuts: &'a [Cut],
+  duration: f64,
+  gaps: Vec<Segment>,
+  restored: Vec<bool>,
+  padding: f64,
+  selected: usize,
+  scroll: usize,
+}
+
+impl Review<'_> {
+  fn edited_cuts(&self) -> Vec<Cut> {
+    let restored: Vec<Cut> = self.gaps.iter().zip(&self.restored)
+      .filter(|(_, restored)| **restored)
+      .map(|(gap, _)| Cut { start: gap.start, end: gap.end })
+      .collect();
+    edit_cuts(self.cuts, &restored, self.padding, self.duration)
+  }
+
+  fn draw(&mut self, term: &Term) -> std::io::Result<()> {
+    let kept: f64 = self.edited_cuts().iter().map(|cut| cut.end - cut.start).sum();
+    let restored = self.restored.iter().filter(|restored| **restored).count();
+
+    // keep the selection visible
+    let rows = (term.size().0 as usize).saturating_sub(8).max(1);
+    if self.selected < self.scroll {
+      self.scroll = self.selected;
+    } else if self.selected >= self.scroll + rows {
+      self.scroll = self.selected + 1 - rows;
+    }
+
+    term.clear_screen()?;
+    term.write_line(&format!("{} {}", style("Review").bold(), style(self.input).yellow()))?;
+    term.write_line(&format!(
+      "Kept {} of {} | Padding {:.1} s | {} of {} gaps restored",
+      style(format_timestamp(kept)).green(), format_timestamp(self.duration), self.padding, restored, self.gaps.len(),
+    ))?;
+    term.write_line("")?;
+
+    if self.gaps.is_empty() {
+      term.write_line("Nothing is removed.")?;
+    }
+    for (i, gap) in self.gaps.iter().enumerate().skip(self.scroll).take(rows) {
+      let marker = if self.restored[i] { style("[keep]").green() } else { style("[cut] ").red() };
+      let line = format!("{} {} - {}  {:>8.1} s", marker, format_timestamp(gap.start), format_timestamp(gap.end), gap.length());
+      if i == self.selected {
+        term.write_line(&format!("{} {}", style(">").cyan().bold(), line))?;
+      } else {
+        term.write_line(&format!("  {}", line))?;
+      }
+    }
+
+    term.write_line("")?;
+    term.write_line(&format!("{}", style("↑/↓ select  space toggle  +/- padding  r render  q abort").dim()))
+  }
+}
+
+/// Lists the removed gaps and lets the user restore gaps and adjust the padding.
+/// Returns the edited cuts, or None if the review was aborted.
+pub fn review_cuts(input: &str, cuts: &[Cut], duration: f64) -> Option<Vec<Cut>> {
+  let term = Term::stdout();
+  let gaps: Vec<Segment> = segments(cuts, duration).into_iter().filter(|segment| !segment.kept).collect();
+  let mut review = Review {
+    input,
+    cuts,
+    duration,
+    restored: vec![false; gaps.len()],
+    gaps,
+    padding: 0.0,
+    selected: 0,
+    scroll: 0,
+  };
+
+  if let Err(e) = term.hide_cursor() {
+    eprintln!("Error: {}", e);
+  }
+  let result = loop {
+    if let Err(e) = review.draw(&term) {
+      eprintln!("Error: {}", e);
+      break None;
+    }
+    let key = match term.read_key() {
+      Ok(key) => key,
+      Err(_) => break None,
+    };
+    match key {
+      Key::ArrowUp | Key::Char('k') => review.selected = review.selected.saturating_sub(1),
+      Key::ArrowDown | Key::Char('j') => review.selected = (review.selected + 1).min(review.gaps.len().saturating_sub(1)),
+      Key::Char(' ') | Key::Enter => {
+        if let Some(restored) = review.restored.get_mut(review.selected) {
+          *restored = !*restored;
+        }
+      },
+      Key::Char('+') | Key::Char('=') => review.padding += PADDING_STEP,
+      Key::Char('-') => review.padding = (review.padding - PADDING_STEP).max(0.0),
+      Key::Char('r') => break Some(review.edited_cuts()),
+      // Ctrl-C does not raise a signal while keys are read
+      Key::Char('q') | Key::Escape | Key::Char('\u{3}') => break None,
+      _ => {},
+    }
+  };
+  if let Err(e) = term.clear_screen().and_then(|_| term.show_cursor()) {
+    eprintln!("Error: {}", e);
+  }
+  result
+}
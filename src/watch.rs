@@ -0,0 +1,98 @@
+extern crate notify;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use self::notify::{EventKind, RecursiveMode, Watcher};
+
+use crate::printer::raise_error;
+
+fn file_size(path: &Path) -> Option<u64> {
+  match path.metadata() {
+    Ok(md) if md.is_file() => Some(md.len()),
+    _ => None,
+  }
+}
+
+fn is_hidden(path: &Path) -> bool {
+  path.file_name().map(|name| name.to_string_lossy().starts_with('.')).unwrap_or(true)
+}
+
+/// Watches `dir` (not recursively) and calls `on_ready` for every file whose size did not change for `settle`.
+/// Files that already exist when the watch starts are handled like new files.
+/// Hidden files are ignored, as they usually are partial uploads.
+pub fn watch_dir<F: FnMut(&Path)>(dir: &Path, settle: Duration, mut on_ready: F) {
+  let (tx, rx) = channel();
+  let mut watcher = match notify::recommended_watcher(tx) {
+    Ok(watcher) => watcher,
+    Err(e) => {
+      raise_error(format!("Could not start watching: {}", e).as_str());
+      return;
+    }
+  };
+  if let Err(e) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+    raise_error(format!("Could not watch {}: {}", dir.display(), e).as_str());
+  }
+
+  // path -> (last seen size, time of the last change)
+  let mut pending: HashMap<PathBuf, (u64, Instant)> = HashMap::new();
+
+  if let Ok(entries) = dir.read_dir() {
+    for entry in entries.flatten() {
+      let path = entry.path();
+      if let Some(size) = file_size(&path) {
+        if !is_hidden(&path) {
+          pending.insert(path, (size, Instant::now()));
+        }
+      }
+    }
+  }
+
+  loop {
+    match rx.recv_timeout(Duration::from_secs(1)) {
+      Ok(Ok(event)) => {
+        for path in event.paths {
+          if matches!(event.kind, EventKind::Remove(_)) {
+            pending.remove(&path);
+          } else if let Some(size) = file_size(&path) {
+            if !is_hidden(&path) {
+              pending.insert(path, (size, Instant::now()));
+            }
+          }
+        }
+      },
+      Ok(Err(e)) => eprintln!("Error: {}", e),
+      Err(RecvTimeoutError::Timeout) => {},
+      Err(RecvTimeoutError::Disconnected) => break,
+    }
+
+    // a file is considered fully written once its size settled
+    let now = Instant::now();
+    let mut ready = Vec::new();
+    pending.retain(|path, (size, changed)| {
+      match file_size(path) {
+        Some(current) if current != *size => {
+          *size = current;
+          *changed = now;
+          true
+        },
+        Some(_) => {
+          if now.duration_since(*changed) >= settle {
+            ready.push(path.clone());
+            false
+          } else {
+            true
+          }
+        },
+        None => false,
+      }
+    });
+
+    ready.sort();
+    for path in ready {
+      on_ready(&path);
+    }
+  }
+}
@@ -0,0 +1,1081 @@
+//! Drives the `lecturecut` binary end-to-end with the mock modules from `tests/fixtures`.
+//!
+//! Modules are loaded from `modules/` next to the executable, so every test copies the binary
+//! and the freshly compiled fixtures into its own directory below `CARGO_TARGET_TMPDIR`.
+
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::time::{Duration, Instant};
+use std::sync::OnceLock;
+
+// just enough of an MP4 header for the mime type detection
+const MP4_HEADER: &[u8] = b"\x00\x00\x00\x18ftypmp42\x00\x00\x00\x00mp42isom\x00\x00\x00\x08free";
+
+fn library_name(name: &str) -> String {
+  format!("{}{}{}", std::env::consts::DLL_PREFIX, name, std::env::consts::DLL_SUFFIX)
+}
+
+/// Compiles the fixture modules once per test run and returns the directory holding them.
+fn fixture_modules() -> &'static Path {
+  static MODULES: OnceLock<PathBuf> = OnceLock::new();
+  MODULES.get_or_init(|| {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("fixture-modules");
+    fs::create_dir_all(&dir).unwrap();
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    for (source, name) in [("mock_generator.rs", "generator"), ("mock_render.rs", "render")] {
+      let status = Command::new(&rustc)
+        .args(["--edition", "2021", "--crate-type", "cdylib", "-O", "-o"])
+        .arg(dir.join(library_name(name)))
+        .arg(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(source))
+        .status()
+        .expect("could not run rustc");
+      assert!(status.success(), "could not compile {}", source);
+    }
+    dir
+  })
+}
+
+/// A private installation of lecturecut with the mock modules.
+struct Sandbox {
+  root: PathBuf,
+}
+
+impl Sandbox {
+  fn new(name: &str, modules: &[&str]) -> Sandbox {
+    let root = Path::new(env!("CARGO_TARGET_TMPDIR")).join("end-to-end").join(name);
+    if root.exists() {
+      fs::remove_dir_all(&root).unwrap();
+    }
+    fs::create_dir_all(root.join("bin/modules")).unwrap();
+    fs::create_dir_all(root.join("work")).unwrap();
+
+    let exe = Path::new(env!("CARGO_BIN_EXE_lecturecut"));
+    fs::copy(exe, root.join("bin").join(exe.file_name().unwrap())).unwrap();
+    for module in modules {
+      let library = library_name(module);
+      fs::copy(fixture_modules().join(&library), root.join("bin/modules").join(&library)).unwrap();
+    }
+    Sandbox { root }
+  }
+
+  fn work(&self, file: &str) -> PathBuf {
+    self.root.join("work").join(file)
+  }
+
+  fn video(&self, file: &str) -> PathBuf {
+    let path = self.work(file);
+    fs::write(&path, MP4_HEADER).unwrap();
+    path
+  }
+
+  fn run(&self, args: &[&str]) -> Output {
+    self.run_with_env(args, &[])
+  }
+
+  /// Starts a long running command like `watch` or `serve`.
+  fn spawn(&self, args: &[&str], env: &[(&str, &str)]) -> Child {
+    let exe = Path::new(env!("CARGO_BIN_EXE_lecturecut"));
+    Command::new(self.root.join("bin").join(exe.file_name().unwrap()))
+      .args(args)
+      .env("XDG_DATA_HOME", self.root.join("data"))
+      .env("XDG_CACHE_HOME", self.root.join("cache"))
+      .envs(env.iter().copied())
+      .current_dir(self.root.join("work"))
+      .stdin(Stdio::null())
+      .stdout(Stdio::piped())
+      .stderr(Stdio::piped())
+      .spawn()
+      .expect("could not run lecturecut")
+  }
+
+  fn run_with_env(&self, args: &[&str], env: &[(&str, &str)]) -> Output {
+    let exe = Path::new(env!("CARGO_BIN_EXE_lecturecut"));
+    Command::new(self.root.join("bin").join(exe.file_name().unwrap()))
+      .args(args)
+      .env("XDG_DATA_HOME", self.root.join("data"))
+      .env("XDG_CACHE_HOME", self.root.join("cache"))
+      .envs(env.iter().copied())
+      .current_dir(self.root.join("work"))
+      .stdin(Stdio::null())
+      .output()
+      .expect("could not run lecturecut")
+  }
+}
+
+/// Waits up to ten seconds for `condition` to hold.
+fn wait_for<F: Fn() -> bool>(condition: F) -> bool {
+  let start = Instant::now();
+  while start.elapsed() < Duration::from_secs(10) {
+    if condition() {
+      return true;
+    }
+    std::thread::sleep(Duration::from_millis(100));
+  }
+  false
+}
+
+/// Stops a long running command and returns what it printed.
+fn stop(mut child: Child) -> Output {
+  child.kill().unwrap();
+  child.wait_with_output().unwrap()
+}
+
+/// Sends a request to the job API and returns the status code and the body of the response.
+fn http(address: &str, method: &str, path: &str, body: &str) -> (u16, String) {
+  let mut stream = std::net::TcpStream::connect(address).unwrap();
+  write!(stream, "{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}", method, path, address, body.len(), body).unwrap();
+  let mut response = String::new();
+  stream.read_to_string(&mut response).unwrap();
+  let status = response.split_whitespace().nth(1).and_then(|status| status.parse().ok()).unwrap_or(0);
+  let body = response.split_once("\r\n\r\n").map(|(_, body)| body.to_string()).unwrap_or_default();
+  (status, body)
+}
+
+fn read(path: &Path) -> String {
+  fs::read_to_string(path).unwrap_or_else(|e| panic!("could not read {}: {}", path.display(), e))
+}
+
+#[test]
+fn tsonly_writes_generated_cuts() {
+  let sandbox = Sandbox::new("tsonly", &["generator", "render"]);
+  sandbox.video("lecture.mp4");
+
+  let output = sandbox.run(&["-i", "lecture.mp4", "-o", "cuts.csv", "--tsonly"]);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  assert_eq!(read(&sandbox.work("cuts.csv")), "1,4\n6,9.5\n12,20\n");
+}
+
+#[test]
+fn render_receives_cuts_and_arguments() {
+  let sandbox = Sandbox::new("render", &["generator", "render"]);
+  sandbox.video("lecture.mp4");
+
+  let output = sandbox.run(&["-i", "lecture.mp4", "-o", "cut.mp4", "-s", "2", "--mock-label", "hello"]);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  assert_eq!(read(&sandbox.work("cut.mp4")), "input=lecture.mp4\ncut=2,8\ncut=12,19\ncut=24,40\narg=mock-label=hello\n");
+  assert!(String::from_utf8_lossy(&output.stdout).contains("mock-render 1.0"));
+}
+
+#[test]
+fn output_name_is_generated_automatically() {
+  let sandbox = Sandbox::new("automatic-output", &["generator", "render"]);
+  sandbox.video("lecture.mp4");
+
+  let output = sandbox.run(&["-i", "lecture.mp4"]);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  assert!(sandbox.work("lecture_lecturecut.mp4").exists());
+}
+
+#[test]
+fn name_template_is_applied() {
+  let sandbox = Sandbox::new("name-template", &["generator", "render"]);
+  sandbox.video("lecture.mp4");
+
+  let output = sandbox.run(&["-i", "lecture.mp4", "--tsonly", "--name-template", "{stem}.{generator}.{ext}"]);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  assert!(sandbox.work("lecture.mock-generator.csv").exists());
+
+  let output = sandbox.run(&["-i", "lecture.mp4", "--name-template", "{stem}_{unknown}.{ext}"]);
+  assert!(!output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("Unknown placeholder {unknown}"));
+}
+
+#[test]
+fn output_template_and_format() {
+  let sandbox = Sandbox::new("output-template", &["generator", "render"]);
+  sandbox.video("lecture.mp4");
+
+  let output = sandbox.run(&["-i", "lecture.mp4", "--output-template", "{dir}/cut/{stem}.cut.{ext}", "--format", "mkv"]);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  assert!(read(&sandbox.work("cut/lecture.cut.mkv")).starts_with("input=lecture.mp4\n"));
+
+  let output = sandbox.run(&["-i", "lecture.mp4", "-o", "cut.mp4", "--format", "webm"]);
+  assert!(!output.status.success());
+}
+
+#[test]
+fn audio_only_needs_capability() {
+  let sandbox = Sandbox::new("audio-only", &["generator", "render"]);
+  sandbox.video("lecture.mp4");
+
+  let output = sandbox.run(&["-i", "lecture.mp4", "--audio-only", "opus"]);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  assert!(sandbox.work("lecture_lecturecut.opus").exists());
+  assert!(String::from_utf8_lossy(&output.stdout).contains("audio"));
+
+  let output = sandbox.run_with_env(&["-i", "lecture.mp4", "-o", "cut.m4a", "--audio-only"], &[("MOCK_RENDER_VIDEO_ONLY", "1")]);
+  assert!(!output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("does not support --audio-only"));
+}
+
+#[test]
+fn chapters_start_after_long_gaps() {
+  let sandbox = Sandbox::new("chapters", &["generator", "render"]);
+  sandbox.video("lecture.mp4");
+
+  // the mock keeps 1-4, 6-9.5 and 12-20, so only the second gap (2.5 s) starts a chapter
+  let output = sandbox.run(&["-i", "lecture.mp4", "-o", "cut.mp4", "--chapters", "2.2"]);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  assert_eq!(read(&sandbox.work("cut.chapters.vtt")), "WEBVTT\n\n1\n00:00:00.000 --> 00:00:06.500\nChapter 1\n\n2\n00:00:06.500 --> 00:00:14.500\nChapter 2\n");
+
+  let output = sandbox.run(&["-i", "lecture.mp4", "-o", "cut.csv", "--tsonly", "--chapters", "1", "--chapter-format", "ffmetadata"]);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  let metadata = read(&sandbox.work("cut.ffmetadata"));
+  assert!(metadata.starts_with(";FFMETADATA1\n"));
+  assert_eq!(metadata.matches("[CHAPTER]").count(), 3);
+  assert!(metadata.contains("START=6500\nEND=14500\n"));
+}
+
+#[test]
+fn timestamp_map_converts_both_ways() {
+  let sandbox = Sandbox::new("timestamp-map", &["generator", "render"]);
+  sandbox.video("lecture.mp4");
+
+  let output = sandbox.run(&["-i", "lecture.mp4", "-o", "cut.mp4", "--timestamp-map"]);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  assert_eq!(read(&sandbox.work("cut.map.csv")), "input_start,input_end,output_start\n1,4,0\n6,9.5,3\n12,20,6.5\n");
+
+  let output = sandbox.run(&["map", "cut.map.csv", "--time", "0:07"]);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  assert!(String::from_utf8_lossy(&output.stdout).contains("Input 00:00:07.000 -> Output 00:00:04.000"));
+
+  let output = sandbox.run(&["map", "cut.map.csv", "--time", "5"]);
+  let stdout = String::from_utf8_lossy(&output.stdout);
+  assert!(stdout.contains("Input 00:00:05.000 -> Output 00:00:03.000"));
+  assert!(stdout.contains("This part was removed"));
+
+  let output = sandbox.run(&["map", "cut.map.csv", "--time", "10", "--to", "input"]);
+  assert!(String::from_utf8_lossy(&output.stdout).contains("Output 00:00:10.000 -> Input 00:00:15.500"));
+
+  let output = sandbox.run(&["map", "cut.map.csv", "--time", "1:00", "--to", "input"]);
+  assert!(!output.status.success());
+}
+
+#[test]
+fn map_works_without_modules() {
+  let sandbox = Sandbox::new("map-offline", &[]);
+  fs::write(sandbox.work("cut.map.csv"), "input_start,input_end,output_start\n1,4,0\n6,9.5,3\n").unwrap();
+
+  let output = sandbox.run(&["map", "cut.map.csv", "--time", "7"]);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  let stdout = String::from_utf8_lossy(&output.stdout);
+  assert!(stdout.contains("Input 00:00:07.000 -> Output 00:00:04.000"));
+  assert!(!stdout.contains("Generator:"));
+}
+
+#[test]
+fn map_and_diff_reject_module_arguments() {
+  let sandbox = Sandbox::new("map-module-args", &["generator", "render"]);
+  fs::write(sandbox.work("cut.map.csv"), "input_start,input_end,output_start\n1,4,0\n6,9.5,3\n").unwrap();
+
+  let output = sandbox.run(&["--mock-scale", "2", "map", "cut.map.csv", "--time", "7"]);
+  assert!(!output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("map and diff take no module arguments."));
+
+  let output = sandbox.run(&["--mock-fail", "diff", "cut.map.csv", "cut.map.csv"]);
+  assert!(!output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("map and diff take no module arguments."));
+}
+
+#[test]
+fn subtitles_are_retimed() {
+  let sandbox = Sandbox::new("subtitles", &["generator", "render"]);
+  sandbox.video("lecture.mp4");
+  // the mock keeps 1-4, 6-9.5 and 12-20
+  fs::write(sandbox.work("lecture.srt"), "1\r\n00:00:00,500 --> 00:00:02,000\r\nHello\r\n\r\n2\r\n00:00:04,500 --> 00:00:05,500\r\nGone\r\n\r\n3\r\n00:00:08,000 --> 00:00:13,000\r\nAcross\r\ntwo lines\r\n").unwrap();
+  fs::write(sandbox.work("lecture.vtt"), "WEBVTT\n\nNOTE not a cue\n\nintro\n00:08.000 --> 00:13.000 align:start\nAcross\n").unwrap();
+
+  let output = sandbox.run(&["-i", "lecture.mp4", "-o", "cut.mp4", "--subtitles", "lecture.srt"]);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  assert_eq!(read(&sandbox.work("cut.srt")), "1\n00:00:00,000 --> 00:00:01,000\nHello\n\n2\n00:00:05,000 --> 00:00:06,500\nAcross\ntwo lines\n\n3\n00:00:06,500 --> 00:00:07,500\nAcross\ntwo lines\n");
+
+  let output = sandbox.run(&["-i", "lecture.mp4", "-o", "cut.mkv", "--subtitles", "lecture.vtt"]);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  assert_eq!(read(&sandbox.work("cut.vtt")), "WEBVTT\n\nintro\n00:00:05.000 --> 00:00:06.500 align:start\nAcross\n\n00:00:06.500 --> 00:00:07.500 align:start\nAcross\n");
+}
+
+#[test]
+fn retimed_subtitles_never_overwrite_files() {
+  let sandbox = Sandbox::new("subtitles-overwrite", &["generator", "render"]);
+  fs::create_dir_all(sandbox.work("raw")).unwrap();
+  sandbox.video("raw/lecture.mp4");
+  fs::write(sandbox.work("lecture.srt"), "1\n00:00:01,500 --> 00:00:02,000\nHello\n").unwrap();
+  fs::write(sandbox.work("cut.srt"), "existing").unwrap();
+
+  let output = sandbox.run(&["-i", "raw/lecture.mp4", "-o", "lecture.mp4", "--subtitles", "lecture.srt"]);
+  assert!(!output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("would overwrite the original subtitles"));
+  assert!(!sandbox.work("lecture.mp4").exists());
+
+  let output = sandbox.run(&["-i", "raw/lecture.mp4", "-o", "cut.mp4", "--subtitles", "lecture.srt"]);
+  assert!(!output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("cut.srt already exists"));
+  assert_eq!(read(&sandbox.work("cut.srt")), "existing");
+  assert_eq!(read(&sandbox.work("lecture.srt")), "1\n00:00:01,500 --> 00:00:02,000\nHello\n");
+}
+
+#[test]
+fn review_page_shows_all_parts() {
+  let sandbox = Sandbox::new("review-html", &["generator", "render"]);
+  sandbox.video("my lecture.mp4");
+
+  let output = sandbox.run(&["-i", "my lecture.mp4", "-o", "cut.mp4", "--review-html", "review.html"]);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  let html = read(&sandbox.work("review.html"));
+  assert!(html.contains("my%20lecture.mp4\" controls"));
+  // 1-4, 6-9.5 and 12-20 are kept from the 30 s the mock reports
+  assert_eq!(html.matches("<div class=\"kept\"").count(), 3);
+  assert_eq!(html.matches("<div class=\"removed\"").count(), 4);
+  assert!(html.contains("onclick=\"jump(9.5)\""));
+  assert!(html.contains("Kept 00:00:14.500 of 00:00:30.000"));
+}
+
+#[test]
+fn review_page_never_overwrites_files() {
+  let sandbox = Sandbox::new("review-html-paths", &["generator", "render"]);
+  sandbox.video("lecture.mp4");
+  fs::write(sandbox.work("notes.html"), "notes").unwrap();
+
+  let output = sandbox.run(&["-i", "lecture.mp4", "-o", "cut.mp4", "--review-html", "lecture.mp4"]);
+  assert!(!output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("would overwrite the input"));
+  assert_eq!(fs::read(sandbox.work("lecture.mp4")).unwrap(), MP4_HEADER);
+
+  let output = sandbox.run(&["-i", "lecture.mp4", "-o", "cut.mp4", "--review-html", "./cut.mp4"]);
+  assert!(!output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("would overwrite the output"));
+
+  let output = sandbox.run(&["-i", "lecture.mp4", "-o", "cut.mp4", "--review-html", "notes.html"]);
+  assert!(!output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("already exists"));
+  assert_eq!(read(&sandbox.work("notes.html")), "notes");
+  assert!(!sandbox.work("cut.mp4").exists());
+}
+
+#[test]
+fn dry_run_only_shows_timeline() {
+  let sandbox = Sandbox::new("dry-run", &["generator", "render"]);
+  sandbox.video("lecture.mp4");
+
+  let output = sandbox.run(&["-i", "lecture.mp4", "--dry-run"]);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  let stdout = String::from_utf8_lossy(&output.stdout);
+  assert!(stdout.contains("1. 00:00:20.000 - 00:00:30.000  10.0 s"));
+  assert!(stdout.contains("2. 00:00:09.500 - 00:00:12.000  2.5 s"));
+  assert!(!stdout.contains("Size Changes"));
+  assert_eq!(fs::read_dir(sandbox.root.join("work")).unwrap().count(), 1);
+  assert!(!sandbox.root.join("cache/lecturecut").exists());
+}
+
+#[test]
+fn directory_dry_run_shows_every_timeline() {
+  let sandbox = Sandbox::new("directory-dry-run", &["generator", "render"]);
+  fs::create_dir_all(sandbox.work("lectures")).unwrap();
+  sandbox.video("lectures/first.mp4");
+  sandbox.video("lectures/second.mp4");
+
+  let output = sandbox.run(&["-i", "lectures", "-o", "out/nested", "--dry-run"]);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  let stdout = String::from_utf8_lossy(&output.stdout);
+  assert!(!stdout.contains("Size Changes"));
+  for name in ["first.mp4", "second.mp4"] {
+    assert!(stdout.contains(format!(" Input: lectures/{}", name).as_str()), "{}", stdout);
+  }
+  assert_eq!(stdout.matches("Longest removed gaps").count(), 2);
+  assert!(!sandbox.work("out").exists());
+  assert!(!sandbox.root.join("cache/lecturecut").exists());
+}
+
+#[test]
+fn stats_are_followed_by_the_timeline() {
+  let sandbox = Sandbox::new("directory-timeline", &["generator", "render"]);
+  fs::create_dir_all(sandbox.work("lectures")).unwrap();
+  sandbox.video("lectures/first.mp4");
+
+  let output = sandbox.run(&["-i", "lectures", "-o", "out", "--tsonly"]);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  let stdout = String::from_utf8_lossy(&output.stdout);
+  let stats = stdout.find("Size Changes").expect("no stats");
+  let timeline = stdout.find("Longest removed gaps").expect("no timeline");
+  assert!(stats < timeline);
+}
+
+#[test]
+fn stdin_output_is_checked_up_front() {
+  let sandbox = Sandbox::new("stdin-supports", &["generator", "render"]);
+
+  let output = sandbox.run_with_env(&["-i", "-", "-o", "cut.webm"], &[("MOCK_RENDER_UNSUPPORTED", "webm")]);
+  assert!(!output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("can not turn stdin (wav with pcm_s16le) into a .webm file"), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn stdin_audio_is_removed_after_failures() {
+  let sandbox = Sandbox::new("stdin-audio", &["generator", "render"]);
+  let tmp = sandbox.root.join("tmp");
+  fs::create_dir_all(&tmp).unwrap();
+  // a second of 16 bit mono silence at 8 kHz
+  let mut wav = Vec::new();
+  for (chunk, value) in [(&b"RIFF"[..], 36 + 16000_u32), (b"WAVEfmt ", 16), (b"", 0x0001_0001), (b"", 8000), (b"", 16000), (b"", 0x0010_0002), (b"data", 16000)] {
+    wav.extend_from_slice(chunk);
+    wav.extend_from_slice(&value.to_le_bytes());
+  }
+  wav.resize(wav.len() + 16000, 0);
+
+  let exe = Path::new(env!("CARGO_BIN_EXE_lecturecut"));
+  for (args, success) in [(&["-i", "-", "-o", "cuts.csv", "--tsonly"][..], true), (&["-i", "-", "-o", "failed.csv", "--tsonly", "--mock-fail"][..], false)] {
+    let mut child = Command::new(sandbox.root.join("bin").join(exe.file_name().unwrap()))
+      .args(args)
+      .env("TMPDIR", &tmp)
+      .env("XDG_CACHE_HOME", sandbox.root.join("cache"))
+      .current_dir(sandbox.root.join("work"))
+      .stdin(Stdio::piped())
+      .stdout(Stdio::piped())
+      .stderr(Stdio::piped())
+      .spawn()
+      .unwrap();
+    child.stdin.take().unwrap().write_all(&wav).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.success(), success, "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(fs::read_dir(&tmp).unwrap().count(), 0);
+  }
+  assert_eq!(read(&sandbox.work("cuts.csv")), "1,4\n6,9.5\n12,20\n");
+}
+
+#[test]
+fn review_needs_a_terminal() {
+  let sandbox = Sandbox::new("review", &["generator", "render"]);
+  sandbox.video("lecture.mp4");
+
+  let output = sandbox.run(&["review", "lecture.mp4", "-o", "cut.mp4"]);
+  assert!(!output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("needs an interactive terminal"));
+  assert!(!sandbox.work("cut.mp4").exists());
+}
+
+#[test]
+fn diff_compares_cut_lists() {
+  // diff needs no module
+  let sandbox = Sandbox::new("diff", &[]);
+  fs::write(sandbox.work("before.csv"), "1,4\n6,9.5\n12,20\n").unwrap();
+  fs::write(sandbox.work("after.csv"), "1,4\n6,10\n22,24\n").unwrap();
+
+  let output = sandbox.run(&["diff", "before.csv", "after.csv"]);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  let stdout = String::from_utf8_lossy(&output.stdout);
+  for expected in ["changed", "removed", "added", "Unchanged segments: 1", "Kept: 00:00:14.500 -> 00:00:09.000 (-5.50 s)", "Overlap: 38.24 %"] {
+    assert!(stdout.contains(expected), "missing {:?} in {}", expected, stdout);
+  }
+  assert!(!stdout.contains("Generator:"));
+}
+
+#[test]
+fn eval_scores_against_labels() {
+  let sandbox = Sandbox::new("eval", &["generator", "render"]);
+  sandbox.video("lecture.mp4");
+  // the mock keeps 1-4, 6-9.5 and 12-20
+  fs::write(sandbox.work("labels.csv"), "start,end\n1,4\n6,10\n12,21\n").unwrap();
+
+  let output = sandbox.run(&["eval", "lecture.mp4", "--truth", "labels.csv"]);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  let stdout = String::from_utf8_lossy(&output.stdout);
+  for expected in ["100.00 %", "90.62 %", "0.9508", "1.50 s", "0.250 s mean, 1.000 s max"] {
+    assert!(stdout.contains(expected), "missing {:?} in {}", expected, stdout);
+  }
+}
+
+#[test]
+fn sweep_runs_every_combination() {
+  let sandbox = Sandbox::new("sweep", &["generator", "render"]);
+  sandbox.video("lecture.mp4");
+
+  let output = sandbox.run(&["sweep", "lecture.mp4", "--arg", "mock-scale=1,2", "--arg", "mock-fail=false"]);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  let stdout = String::from_utf8_lossy(&output.stdout);
+  // 14.5 s of 30 s are kept, scaling only changes the durations
+  assert!(stdout.contains("00:00:14.500"), "{}", stdout);
+  assert!(stdout.contains("00:00:29.000"), "{}", stdout);
+  assert_eq!(stdout.matches("48.33 %").count(), 2, "{}", stdout);
+
+  let output = sandbox.run(&["sweep", "lecture.mp4", "--arg", "unknown=1,2"]);
+  assert!(!output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("no argument unknown"));
+}
+
+#[test]
+fn directory_mode_honors_output_template() {
+  let sandbox = Sandbox::new("directory-template", &["generator", "render"]);
+  fs::create_dir_all(sandbox.work("lectures")).unwrap();
+  sandbox.video("lectures/first.mp4");
+
+  let output = sandbox.run(&["-i", "lectures", "-o", "out", "--output-template", "{dir}/{stem}.{preset}.{ext}", "--format", "webm"]);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  assert!(sandbox.work("out/first.default.webm").exists());
+}
+
+#[test]
+fn directory_mode_processes_every_file() {
+  let sandbox = Sandbox::new("directory", &["generator", "render"]);
+  fs::create_dir_all(sandbox.work("lectures")).unwrap();
+  sandbox.video("lectures/first.mp4");
+  sandbox.video("lectures/second.mp4");
+
+  let output = sandbox.run(&["-i", "lectures", "-o", "out", "--tsonly"]);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  for name in ["first", "second"] {
+    let csv = fs::read_dir(sandbox.work("out")).unwrap()
+      .filter_map(|entry| entry.ok())
+      .find(|entry| entry.file_name().to_string_lossy().starts_with(name))
+      .unwrap_or_else(|| panic!("no output for {}", name));
+    assert_eq!(read(&csv.path()), "1,4\n6,9.5\n12,20\n");
+  }
+}
+
+#[test]
+fn input_list_processes_listed_files() {
+  let sandbox = Sandbox::new("input-list", &["generator", "render"]);
+  fs::create_dir_all(sandbox.work("week 1")).unwrap();
+  fs::create_dir_all(sandbox.work("week 2")).unwrap();
+  sandbox.video("week 1/monday.mp4");
+  sandbox.video("week 2/tuesday.mp4");
+  fs::write(sandbox.work("inputs.txt"), "# this week\nweek 1/monday.mp4\nweek 2/tuesday.mp4\ttuesday.csv\nweek 3/missing.mp4\n").unwrap();
+
+  let output = sandbox.run(&["--input-list", "inputs.txt", "-o", "out", "--tsonly"]);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  assert_eq!(read(&sandbox.work("out/week 1/monday.csv")), "1,4\n6,9.5\n12,20\n");
+  assert_eq!(read(&sandbox.work("out/tuesday.csv")), "1,4\n6,9.5\n12,20\n");
+  assert!(String::from_utf8_lossy(&output.stderr).contains("Skipping week 3/missing.mp4"));
+  let stdout = String::from_utf8_lossy(&output.stdout);
+  assert!(stdout.contains("monday.mp4") && stdout.contains("tuesday.mp4"));
+}
+
+#[test]
+fn input_list_keeps_files_with_the_same_name_apart() {
+  let sandbox = Sandbox::new("input-list-names", &["generator", "render"]);
+  fs::create_dir_all(sandbox.work("week 1")).unwrap();
+  fs::create_dir_all(sandbox.work("week 2")).unwrap();
+  sandbox.video("week 1/lecture.mp4");
+  sandbox.video("week 2/lecture.mp4");
+  fs::write(sandbox.work("inputs.txt"), "week 1/lecture.mp4\nweek 2/lecture.mp4\n").unwrap();
+
+  let output = sandbox.run(&["--input-list", "inputs.txt", "-o", "out", "--tsonly"]);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  assert!(sandbox.work("out/week 1/lecture.csv").exists());
+  assert!(sandbox.work("out/week 2/lecture.csv").exists());
+
+  fs::write(sandbox.work("inputs.txt"), "week 1/lecture.mp4\tcut.csv\nweek 2/lecture.mp4\tcut.csv\n").unwrap();
+  let output = sandbox.run(&["--input-list", "inputs.txt", "-o", "other", "--tsonly"]);
+  assert!(!output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("week 1/lecture.mp4 and week 2/lecture.mp4 would both be written to other/cut.csv"));
+  assert!(!sandbox.work("other/cut.csv").exists());
+}
+
+#[test]
+fn input_list_outputs_are_validated_up_front() {
+  let sandbox = Sandbox::new("input-list-validation", &["generator", "render"]);
+  sandbox.video("first.mp4");
+  sandbox.video("second.mp4");
+  fs::write(sandbox.work("inputs.txt"), "first.mp4\nsecond.mp4\tsecond.mp4\n").unwrap();
+
+  let output = sandbox.run(&["--input-list", "inputs.txt", "-o", "out", "--format", "webm"]);
+  assert!(!output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("The output out/second.mp4 of second.mp4 needs the .webm extension"));
+  assert!(!sandbox.work("out/first.webm").exists());
+
+  fs::write(sandbox.work("taken"), "").unwrap();
+  let output = sandbox.run(&["--input-list", "inputs.txt", "-o", "taken"]);
+  assert!(!output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("needs to be a directory"));
+}
+
+/// Installs an ffprobe replacement printing `<input>.json`, and returns the `PATH` to use it.
+fn fake_ffprobe(sandbox: &Sandbox) -> String {
+  let script = sandbox.root.join("bin/ffprobe");
+  // every probed file is logged, so tests can tell how often a file was probed
+  fs::write(&script, "#!/bin/sh\nfor last; do :; done\necho \"$last\" >> \"$0.log\"\nexec cat \"$last.json\"\n").unwrap();
+  let mut permissions = fs::metadata(&script).unwrap().permissions();
+  std::os::unix::fs::PermissionsExt::set_mode(&mut permissions, 0o755);
+  fs::set_permissions(&script, permissions).unwrap();
+  format!("{}:{}", sandbox.root.join("bin").display(), std::env::var("PATH").unwrap_or_default())
+}
+
+const PROBED_LECTURE: &str = r#"{
+  "streams": [
+    { "index": 0, "codec_type": "video", "codec_name": "h264" },
+    { "index": 1, "codec_type": "audio", "codec_name": "aac" },
+    { "index": 2, "codec_type": "subtitle", "codec_name": "mov_text" }
+  ],
+  "format": { "format_name": "mov,mp4,m4a,3gp,3g2,mj2", "duration": "120.000000" }
+}"#;
+
+#[test]
+#[cfg(unix)]
+fn probed_streams_and_duration_are_reported() {
+  let sandbox = Sandbox::new("probe", &["generator", "render"]);
+  sandbox.video("lecture.mp4");
+  fs::write(sandbox.work("lecture.mp4.json"), PROBED_LECTURE).unwrap();
+  let path = fake_ffprobe(&sandbox);
+
+  let output = sandbox.run_with_env(&["-i", "lecture.mp4", "-o", "cuts.csv", "--tsonly", "--no-cache"], &[("PATH", path.as_str()), ("MOCK_GENERATOR_NO_STATS", "1")]);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  assert!(String::from_utf8_lossy(&output.stderr).contains("Stream #2 (subtitle mov_text) is not supported"));
+  assert!(String::from_utf8_lossy(&output.stdout).contains("2 min 0 sec -> 0 min 14 sec"));
+}
+
+#[test]
+#[cfg(unix)]
+fn single_files_are_probed_once() {
+  let sandbox = Sandbox::new("probe-once", &["generator", "render"]);
+  sandbox.video("podcast.mp4");
+  fs::write(sandbox.work("podcast.mp4.json"), r#"{ "streams": [{ "index": 0, "codec_type": "audio", "codec_name": "aac" }], "format": { "format_name": "mov,mp4" } }"#).unwrap();
+  let path = fake_ffprobe(&sandbox);
+
+  let output = sandbox.run_with_env(&["-i", "podcast.mp4", "-o", "cuts.csv", "--tsonly"], &[("PATH", path.as_str())]);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  assert_eq!(read(&sandbox.root.join("bin/ffprobe.log")).lines().count(), 1);
+  let printed = format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+  assert_eq!(printed.matches("no video stream").count(), 1, "{}", printed);
+  assert!(!printed.contains("not a video file"));
+}
+
+#[test]
+#[cfg(unix)]
+fn directory_mode_skips_files_without_video() {
+  let sandbox = Sandbox::new("probe-directory", &["generator", "render"]);
+  fs::create_dir_all(sandbox.work("lectures")).unwrap();
+  sandbox.video("lectures/lecture.mp4");
+  sandbox.video("lectures/podcast.mp4");
+  fs::write(sandbox.work("lectures/lecture.mp4.json"), PROBED_LECTURE).unwrap();
+  fs::write(sandbox.work("lectures/podcast.mp4.json"), r#"{ "streams": [{ "index": 0, "codec_type": "audio", "codec_name": "aac" }], "format": { "format_name": "mov,mp4" } }"#).unwrap();
+  let path = fake_ffprobe(&sandbox);
+
+  let output = sandbox.run_with_env(&["-i", "lectures", "-o", "out", "--tsonly"], &[("PATH", path.as_str())]);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  assert!(sandbox.work("out/lecture.csv").exists());
+  assert!(!sandbox.work("out/podcast.csv").exists());
+}
+
+#[test]
+fn unsupported_output_format_is_rejected_up_front() {
+  let sandbox = Sandbox::new("supports-format", &["generator", "render"]);
+  sandbox.video("lecture.mp4");
+
+  let output = sandbox.run_with_env(&["-i", "lecture.mp4", "--format", "webm"], &[("MOCK_RENDER_UNSUPPORTED", "webm")]);
+  assert!(!output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("can not turn lecture.mp4 (mp4) into a .webm file"));
+  assert!(!sandbox.work("lecture_lecturecut.webm").exists());
+
+  let output = sandbox.run_with_env(&["-i", "lecture.mp4", "--format", "webm", "--tsonly"], &[("MOCK_RENDER_UNSUPPORTED", "webm")]);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+#[cfg(unix)]
+fn unsupported_codec_is_rejected_up_front() {
+  let sandbox = Sandbox::new("supports-codec", &["generator", "render"]);
+  fs::create_dir_all(sandbox.work("lectures")).unwrap();
+  sandbox.video("lectures/first.mp4");
+  sandbox.video("lectures/second.mp4");
+  fs::write(sandbox.work("lectures/first.mp4.json"), PROBED_LECTURE.replace("aac", "mp3")).unwrap();
+  fs::write(sandbox.work("lectures/second.mp4.json"), PROBED_LECTURE).unwrap();
+  let path = fake_ffprobe(&sandbox);
+
+  let output = sandbox.run_with_env(&["-i", "lectures", "-o", "out"], &[("PATH", path.as_str()), ("MOCK_RENDER_UNSUPPORTED", "aac")]);
+  assert!(!output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("second.mp4 (mp4 with h264, aac, mov_text) into a .mp4 file"));
+  assert!(!sandbox.work("out/first.mp4").exists());
+}
+
+#[test]
+fn manifest_outputs_are_checked_up_front() {
+  let sandbox = Sandbox::new("supports-manifest", &["generator", "render"]);
+  fs::create_dir_all(sandbox.work("lectures")).unwrap();
+  sandbox.video("lectures/first.mp4");
+  sandbox.video("lectures/second.mp4");
+  fs::write(sandbox.work("manifest.csv"), "file,output\nsecond.mp4,second.webm\n").unwrap();
+
+  let output = sandbox.run_with_env(&["-i", "lectures", "-o", "out", "--manifest", "manifest.csv"], &[("MOCK_RENDER_UNSUPPORTED", "webm")]);
+  assert!(!output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("can not turn lectures/second.mp4 (mp4) into a .webm file"));
+  assert!(!sandbox.work("out/first.mp4").exists());
+}
+
+#[test]
+fn input_list_outputs_are_checked_up_front() {
+  let sandbox = Sandbox::new("supports-input-list", &["generator", "render"]);
+  sandbox.video("first.mp4");
+  sandbox.video("second.mp4");
+  fs::write(sandbox.work("inputs.txt"), "first.mp4\nsecond.mp4\tsecond.webm\n").unwrap();
+
+  let output = sandbox.run_with_env(&["--input-list", "inputs.txt", "-o", "out"], &[("MOCK_RENDER_UNSUPPORTED", "webm")]);
+  assert!(!output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("can not turn second.mp4 (mp4) into a .webm file"));
+  assert!(!sandbox.work("out/first.mp4").exists());
+}
+
+#[test]
+fn queue_run_marks_unsupported_jobs_as_failed() {
+  let sandbox = Sandbox::new("supports-queue", &["generator", "render"]);
+  sandbox.video("lecture.mp4");
+  for output in ["cut.webm", "cut.mp4"] {
+    let output = sandbox.run(&["queue", "add", "lecture.mp4", "-o", output]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  }
+
+  // the render module changed its mind since the jobs were added
+  let output = sandbox.run_with_env(&["queue", "run"], &[("MOCK_RENDER_UNSUPPORTED", "webm")]);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  assert!(String::from_utf8_lossy(&output.stderr).contains("Job 1 failed: The render module can not turn"));
+  assert!(!sandbox.work("cut.webm").exists());
+  assert!(sandbox.work("cut.mp4").exists());
+  let queue = read(&sandbox.root.join("data/lecturecut/queue.json"));
+  assert!(queue.contains(r#""status": "failed""#) && queue.contains(r#""status": "done""#), "{}", queue);
+}
+
+#[test]
+fn manifest_overrides_single_files() {
+  let sandbox = Sandbox::new("manifest", &["generator", "render"]);
+  fs::create_dir_all(sandbox.work("lectures")).unwrap();
+  sandbox.video("lectures/first.mp4");
+  sandbox.video("lectures/second.mp4");
+  fs::write(sandbox.work("manifest.csv"), "file,output,trim_start,trim_end,mock-scale\nfirst.mp4,intro.csv,2,12,\nsecond.mp4,,,,2\n").unwrap();
+
+  let output = sandbox.run(&["-i", "lectures", "-o", "out", "--tsonly", "--manifest", "manifest.csv"]);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  assert_eq!(read(&sandbox.work("out/intro.csv")), "2,4\n6,9.5\n12,18\n");
+  assert_eq!(read(&sandbox.work("out/second.csv")), "2,8\n12,19\n24,40\n");
+
+  fs::write(sandbox.work("manifest.toml"), "[[file]]\nname = \"first.mp4\"\noutput = \"scaled.csv\"\nargs = { mock-scale = 2 }\n\n[[file]]\nname = \"missing.mp4\"\n").unwrap();
+  let output = sandbox.run(&["-i", "lectures", "-o", "out", "--tsonly", "--manifest", "manifest.toml"]);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  assert_eq!(read(&sandbox.work("out/scaled.csv")), "2,8\n12,19\n24,40\n");
+  assert!(String::from_utf8_lossy(&output.stderr).contains("missing.mp4"));
+}
+
+#[test]
+fn manifest_rejects_unknown_arguments() {
+  let sandbox = Sandbox::new("manifest-unknown", &["generator", "render"]);
+  fs::create_dir_all(sandbox.work("lectures")).unwrap();
+  sandbox.video("lectures/first.mp4");
+  fs::write(sandbox.work("manifest.csv"), "file,volume\nfirst.mp4,3\n").unwrap();
+
+  let output = sandbox.run(&["-i", "lectures", "-o", "out", "--tsonly", "--manifest", "manifest.csv"]);
+  assert!(!output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("Unknown argument volume"));
+}
+
+#[test]
+fn manifest_rejects_duplicate_files() {
+  let sandbox = Sandbox::new("manifest-duplicate", &["generator", "render"]);
+  fs::create_dir_all(sandbox.work("lectures")).unwrap();
+  sandbox.video("lectures/first.mp4");
+  fs::write(sandbox.work("manifest.csv"), "file,mock-scale\nfirst.mp4,2\nfirst.mp4,3\n").unwrap();
+
+  let output = sandbox.run(&["-i", "lectures", "-o", "out", "--tsonly", "--manifest", "manifest.csv"]);
+  assert!(!output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("first.mp4 is listed more than once"));
+  assert!(!sandbox.work("out/first.mp4").exists());
+}
+
+#[test]
+fn manifest_outputs_stay_in_the_output_directory() {
+  let sandbox = Sandbox::new("manifest-escape", &["generator", "render"]);
+  fs::create_dir_all(sandbox.work("lectures")).unwrap();
+  sandbox.video("lectures/first.mp4");
+  let escaped = sandbox.root.join("escaped.mp4");
+
+  for output in ["../../escaped.mp4", escaped.to_str().unwrap()] {
+    fs::write(sandbox.work("manifest.csv"), format!("file,output\nfirst.mp4,{}\n", output)).unwrap();
+    let output = sandbox.run(&["-i", "lectures", "-o", "out", "--manifest", "manifest.csv"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("needs to be a path inside the output directory"));
+    assert!(!escaped.exists());
+  }
+}
+
+#[test]
+fn manifest_outputs_may_not_collide() {
+  let sandbox = Sandbox::new("manifest-collide", &["generator", "render"]);
+  fs::create_dir_all(sandbox.work("lectures")).unwrap();
+  sandbox.video("lectures/first.mp4");
+  sandbox.video("lectures/second.mp4");
+  fs::write(sandbox.work("manifest.csv"), "file,output\nfirst.mp4,second.mp4\n").unwrap();
+
+  let output = sandbox.run(&["-i", "lectures", "-o", "out", "--manifest", "manifest.csv"]);
+  assert!(!output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("would both be written to out/second.mp4"));
+  assert!(!sandbox.work("out/second.mp4").exists());
+}
+
+#[test]
+fn manifest_outputs_match_the_format() {
+  let sandbox = Sandbox::new("manifest-format", &["generator", "render"]);
+  fs::create_dir_all(sandbox.work("lectures")).unwrap();
+  sandbox.video("lectures/first.mp4");
+  fs::write(sandbox.work("manifest.csv"), "file,output\nfirst.mp4,first.mp4\n").unwrap();
+
+  let output = sandbox.run(&["-i", "lectures", "-o", "out", "--format", "mkv", "--manifest", "manifest.csv"]);
+  assert!(!output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("needs the .mkv extension"));
+  assert!(!sandbox.work("out/first.mp4").exists());
+}
+
+#[test]
+fn trimming_needs_a_known_duration() {
+  let sandbox = Sandbox::new("trim-unknown-duration", &["generator", "render"]);
+  fs::create_dir_all(sandbox.work("lectures")).unwrap();
+  sandbox.video("lectures/first.mp4");
+  fs::write(sandbox.work("manifest.csv"), "file,trim_start\nfirst.mp4,2\n").unwrap();
+  let path = sandbox.root.join("bin").to_string_lossy().to_string();
+
+  let output = sandbox.run_with_env(&["-i", "lectures", "-o", "out", "--tsonly", "--no-cache", "--manifest", "manifest.csv"], &[("PATH", path.as_str()), ("MOCK_GENERATOR_NO_STATS", "1")]);
+  assert!(!output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("duration of lectures/first.mp4 is unknown"));
+  assert!(!sandbox.work("out/first.mp4").exists());
+}
+
+#[test]
+fn watcher_survives_broken_files() {
+  let sandbox = Sandbox::new("watch", &["generator", "render"]);
+  fs::create_dir_all(sandbox.work("inbox")).unwrap();
+  sandbox.video("inbox/broken.mp4");
+  let mut video = MP4_HEADER.to_vec();
+  video.resize(3 * 1024 * 1024, 0);
+  fs::write(sandbox.work("inbox/lecture.mp4"), video).unwrap();
+
+  let child = sandbox.spawn(&["watch", "inbox", "--settle", "0", "--tsonly"], &[("MOCK_GENERATOR_FAIL_ON", "broken")]);
+  let archived = wait_for(|| sandbox.work("inbox/archive/lecture.mp4").exists());
+  let output = stop(child);
+  assert!(archived, "{}", String::from_utf8_lossy(&output.stderr));
+
+  assert!(sandbox.work("inbox/broken.mp4").exists());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("Could not process broken.mp4: mock generator failed"));
+  assert!(String::from_utf8_lossy(&output.stdout).contains("3.00 MB"));
+}
+
+#[test]
+fn watcher_checks_files_on_arrival() {
+  let sandbox = Sandbox::new("watch-supports", &["generator", "render"]);
+  fs::create_dir_all(sandbox.work("inbox")).unwrap();
+  sandbox.video("inbox/unsupported.mkv");
+  sandbox.video("inbox/lecture.mp4");
+
+  let child = sandbox.spawn(&["watch", "inbox", "--settle", "0"], &[("MOCK_RENDER_UNSUPPORTED", "mkv")]);
+  let archived = wait_for(|| sandbox.work("inbox/archive/lecture.mp4").exists());
+  // both files are ready at once, so the other one is done by now as well
+  std::thread::sleep(Duration::from_secs(1));
+  let output = stop(child);
+  assert!(archived, "{}", String::from_utf8_lossy(&output.stderr));
+
+  assert!(sandbox.work("inbox/unsupported.mkv").exists());
+  assert!(!sandbox.work("inbox/lecturecut/unsupported_lecturecut.mkv").exists());
+  assert!(sandbox.work("inbox/lecturecut/lecture_lecturecut.mp4").exists());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("Could not process unsupported.mkv: The render module can not turn"));
+}
+
+#[test]
+fn server_reports_failed_jobs_and_keeps_serving() {
+  let sandbox = Sandbox::new("serve", &["generator", "render"]);
+  sandbox.video("broken.mp4");
+  sandbox.video("lecture.mp4");
+  let address = {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().to_string()
+  };
+
+  let child = sandbox.spawn(&["serve", "--bind", address.as_str()], &[("MOCK_GENERATOR_FAIL_ON", "broken")]);
+  assert!(wait_for(|| std::net::TcpStream::connect(address.as_str()).is_ok()));
+
+  let (status, body) = http(&address, "POST", "/jobs", r#"{ "input": "broken.mp4", "tsonly": true }"#);
+  assert_eq!(status, 201, "{}", body);
+  let (status, body) = http(&address, "POST", "/jobs", r#"{ "input": "lecture.mp4", "output": "cuts.csv", "tsonly": true, "generator_args": { "mock-scale": "2" } }"#);
+  assert_eq!(status, 201, "{}", body);
+  let (status, body) = http(&address, "POST", "/jobs", r#"{ "input": "missing.mp4" }"#);
+  assert_eq!(status, 400, "{}", body);
+
+  let finished = wait_for(|| http(&address, "GET", "/jobs/1", "").1.contains(r#""status":"done""#));
+  let (_, failed) = http(&address, "GET", "/jobs/0", "");
+  let (status, cuts) = http(&address, "GET", "/jobs/1/cuts", "");
+  let (failed_status, _) = http(&address, "GET", "/jobs/0/cuts", "");
+  let output = stop(child);
+  assert!(finished, "{}", String::from_utf8_lossy(&output.stderr));
+
+  assert!(failed.contains(r#""status":"failed""#) && failed.contains("mock generator failed"), "{}", failed);
+  assert_eq!(failed_status, 409);
+  assert_eq!(status, 200);
+  assert_eq!(cuts, r#"[{"start":2.0,"end":8.0},{"start":12.0,"end":19.0},{"start":24.0,"end":40.0}]"#);
+  assert_eq!(read(&sandbox.work("cuts.csv")), "2,8\n12,19\n24,40\n");
+}
+
+#[test]
+fn server_rejects_unsupported_jobs() {
+  let sandbox = Sandbox::new("serve-supports", &["generator", "render"]);
+  sandbox.video("lecture.mp4");
+  let address = {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().to_string()
+  };
+
+  let child = sandbox.spawn(&["serve", "--bind", address.as_str()], &[("MOCK_RENDER_UNSUPPORTED", "webm")]);
+  assert!(wait_for(|| std::net::TcpStream::connect(address.as_str()).is_ok()));
+
+  let (status, body) = http(&address, "POST", "/jobs", r#"{ "input": "lecture.mp4", "output": "x.webm" }"#);
+  let (jobs_status, jobs) = http(&address, "GET", "/jobs", "");
+  stop(child);
+  assert_eq!(status, 400, "{}", body);
+  assert!(body.contains("can not turn lecture.mp4 (mp4) into a .webm file"), "{}", body);
+  assert_eq!((jobs_status, jobs.as_str()), (200, "[]"));
+  assert!(!sandbox.work("x.webm").exists());
+}
+
+#[test]
+fn server_is_not_held_up_by_slow_clients() {
+  let sandbox = Sandbox::new("serve-slow-client", &["generator", "render"]);
+  sandbox.video("lecture.mp4");
+  let address = {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().to_string()
+  };
+
+  let child = sandbox.spawn(&["serve", "--bind", address.as_str()], &[]);
+  assert!(wait_for(|| std::net::TcpStream::connect(address.as_str()).is_ok()));
+
+  // announces a body that never arrives
+  let mut slow = std::net::TcpStream::connect(address.as_str()).unwrap();
+  write!(slow, "POST /jobs HTTP/1.1\r\nHost: {}\r\nContent-Length: 100000\r\n\r\n{{", address).unwrap();
+  slow.flush().unwrap();
+
+  let (tx, rx) = std::sync::mpsc::channel();
+  let request_address = address.clone();
+  std::thread::spawn(move || {
+    let _ = tx.send(http(&request_address, "POST", "/jobs", r#"{ "input": "lecture.mp4", "output": "cuts.csv", "tsonly": true }"#));
+  });
+  let response = rx.recv_timeout(Duration::from_secs(10));
+  let finished = wait_for(|| sandbox.work("cuts.csv").exists());
+  drop(slow);
+  let output = stop(child);
+
+  let (status, body) = response.expect("the server did not answer while another client was slow");
+  assert_eq!(status, 201, "{}", body);
+  assert!(finished, "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn concurrent_queue_commands_keep_every_job() {
+  let sandbox = Sandbox::new("queue-concurrent", &["generator", "render"]);
+  sandbox.video("lecture.mp4");
+
+  let children: Vec<Child> = (0..8)
+    .map(|i| sandbox.spawn(&["queue", "add", "lecture.mp4", "-o", format!("cut{}.csv", i).as_str(), "--tsonly"], &[]))
+    .collect();
+  for child in children {
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  }
+
+  let queue = read(&sandbox.root.join("data/lecturecut/queue.json"));
+  for id in 1..=8 {
+    assert!(queue.contains(format!("\"id\": {},", id).as_str()), "{}", queue);
+  }
+}
+
+#[test]
+#[cfg(unix)]
+fn queue_run_only_requeues_jobs_of_stopped_workers() {
+  let sandbox = Sandbox::new("queue-requeue", &["generator", "render"]);
+  sandbox.video("lecture.mp4");
+  let queue_dir = sandbox.root.join("data/lecturecut");
+  fs::create_dir_all(queue_dir.join("workers")).unwrap();
+
+  // this test plays a worker that is still busy with the first job
+  let busy_worker = std::process::id();
+  let busy_lock = fs::File::create(queue_dir.join("workers").join(format!("{}.lock", busy_worker))).unwrap();
+  busy_lock.lock().unwrap();
+  let job = |id: u32, output: &str, worker: u32| format!(
+    r#"{{ "id": {}, "priority": 0, "status": "running", "input": "{}", "output": "{}", "tsonly": true, "generator_args": [], "render_args": [], "stats": null, "worker": {} }}"#,
+    id, sandbox.work("lecture.mp4").display(), sandbox.work(output).display(), worker,
+  );
+  fs::write(queue_dir.join("queue.json"), format!("[{}, {}]", job(1, "busy.csv", busy_worker), job(2, "stopped.csv", u32::MAX))).unwrap();
+
+  let output = sandbox.run(&["queue", "run"]);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  assert!(!sandbox.work("busy.csv").exists());
+  assert_eq!(read(&sandbox.work("stopped.csv")), "1,4\n6,9.5\n12,20\n");
+  let queue = read(&queue_dir.join("queue.json"));
+  assert!(queue.contains(r#""status": "running""#) && queue.contains(r#""status": "done""#), "{}", queue);
+}
+
+#[test]
+fn second_run_uses_cache() {
+  let sandbox = Sandbox::new("cache", &["generator", "render"]);
+  sandbox.video("lecture.mp4");
+
+  let first = sandbox.run(&["-i", "lecture.mp4", "-o", "first.csv", "--tsonly"]);
+  assert!(first.status.success());
+  let entries: Vec<_> = fs::read_dir(sandbox.root.join("cache/lecturecut")).unwrap().filter_map(|entry| entry.ok()).collect();
+  assert_eq!(entries.len(), 1);
+
+  // the mock generator never produces this, so it can only come from the cache
+  fs::write(entries[0].path(), "30,2\n3,5\n").unwrap();
+  let second = sandbox.run(&["-i", "lecture.mp4", "-o", "second.csv", "--tsonly"]);
+  assert!(second.status.success());
+  assert_eq!(read(&sandbox.work("second.csv")), "3,5\n");
+
+  let uncached = sandbox.run(&["-i", "lecture.mp4", "-o", "third.csv", "--tsonly", "--no-cache"]);
+  assert!(uncached.status.success());
+  assert_eq!(read(&sandbox.work("third.csv")), read(&sandbox.work("first.csv")));
+}
+
+#[test]
+fn module_errors_abort_the_run() {
+  let sandbox = Sandbox::new("module-error", &["generator", "render"]);
+  sandbox.video("lecture.mp4");
+
+  let output = sandbox.run(&["-i", "lecture.mp4", "-o", "cut.mp4", "--mock-fail"]);
+  assert!(!output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("mock generator failed"));
+  assert!(!sandbox.work("cut.mp4").exists());
+}
+
+#[test]
+fn missing_render_module_is_reported() {
+  let sandbox = Sandbox::new("missing-render", &["generator"]);
+  sandbox.video("lecture.mp4");
+
+  let output = sandbox.run(&["-i", "lecture.mp4", "-o", "cut.mp4"]);
+  assert!(!output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("Please compile the render module first"));
+}
+
+#[test]
+fn missing_generator_module_falls_back_to_builtin() {
+  let sandbox = Sandbox::new("builtin-generator", &["render"]);
+  // one second of silence followed by one second of a loud square wave, as 8 kHz 8 bit WAV
+  let mut samples = vec![128u8; 8000];
+  samples.extend((0..8000).map(|i| if i % 16 < 8 { 32 } else { 224 }));
+  let mut wav = Vec::new();
+  wav.extend(b"RIFF");
+  wav.extend((36 + samples.len() as u32).to_le_bytes());
+  wav.extend(b"WAVEfmt ");
+  wav.extend(16u32.to_le_bytes());
+  wav.extend(1u16.to_le_bytes());
+  wav.extend(1u16.to_le_bytes());
+  wav.extend(8000u32.to_le_bytes());
+  wav.extend(8000u32.to_le_bytes());
+  wav.extend(1u16.to_le_bytes());
+  wav.extend(8u16.to_le_bytes());
+  wav.extend(b"data");
+  wav.extend((samples.len() as u32).to_le_bytes());
+  wav.extend(samples);
+  fs::write(sandbox.work("lecture.wav"), wav).unwrap();
+
+  let output = sandbox.run(&["-i", "lecture.wav", "-o", "cuts.csv", "--tsonly"]);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  assert_eq!(read(&sandbox.work("cuts.csv")), "1,2\n");
+
+  let output = sandbox.run(&["-i", "lecture.wav", "--tsonly", "--name-template", "{stem}.{generator}.{ext}"]);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  assert!(sandbox.work("lecture.builtin-energy.csv").exists());
+}
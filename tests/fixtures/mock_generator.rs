@@ -0,0 +1,159 @@
+//! Deterministic generator module used by the integration tests.
+//! It does not look at the input and always keeps the same segments, scaled by `--mock-scale`.
+
+use std::ffi::{c_char, c_double, c_long, CStr};
+
+#[repr(C)]
+pub struct Cut {
+  pub start: c_double,
+  pub end: c_double,
+}
+
+#[repr(C)]
+pub struct CutList {
+  pub length: c_long,
+  pub cuts: *const Cut,
+}
+
+#[repr(C)]
+pub struct GeneratorStats {
+  pub len_pre_cut: c_double,
+  pub len_post_cut: c_double,
+}
+
+#[repr(C)]
+pub struct GeneratorResult {
+  pub cuts: CutList,
+  pub stats: GeneratorStats,
+}
+
+#[repr(C)]
+pub struct CArgument {
+  pub short: c_char,
+  pub long: *const c_char,
+  pub description: *const c_char,
+  pub required: bool,
+  pub is_flag: bool,
+}
+
+#[repr(C)]
+pub struct CArgumentList {
+  pub length: c_long,
+  pub arguments: *const CArgument,
+}
+
+#[repr(C)]
+pub struct CArgumentResult {
+  pub long: *const c_char,
+  pub value: *const c_char,
+}
+
+#[repr(C)]
+pub struct CArgumentResultList {
+  pub length: c_long,
+  pub results: *const CArgumentResult,
+}
+
+type ErrorCallback = unsafe extern "C" fn(*const c_char);
+type ProgressCallback = unsafe extern "C" fn(*const c_char, c_double);
+type CancelCallback = unsafe extern "C" fn() -> bool;
+
+const SEGMENTS: [(f64, f64); 3] = [(1.0, 4.0), (6.0, 9.5), (12.0, 20.0)];
+const DURATION: f64 = 30.0;
+
+struct SyncArguments([CArgument; 2]);
+
+unsafe impl Sync for SyncArguments {}
+
+static ARGUMENTS: SyncArguments = SyncArguments([
+  CArgument {
+    short: b's' as c_char,
+    long: c"mock-scale".as_ptr(),
+    description: c"Factor all timestamps are multiplied with".as_ptr(),
+    required: false,
+    is_flag: false,
+  },
+  CArgument {
+    short: 0,
+    long: c"mock-fail".as_ptr(),
+    description: c"Report an error instead of generating cuts".as_ptr(),
+    required: false,
+    is_flag: true,
+  },
+]);
+
+#[no_mangle]
+pub extern "C" fn init(_error: ErrorCallback) {}
+
+#[no_mangle]
+pub extern "C" fn name(_error: ErrorCallback) -> *const c_char {
+  c"mock-generator".as_ptr()
+}
+
+#[no_mangle]
+pub extern "C" fn version(_error: ErrorCallback) -> *const c_char {
+  c"1.0".as_ptr()
+}
+
+#[no_mangle]
+pub extern "C" fn get_arguments(_error: ErrorCallback) -> CArgumentList {
+  CArgumentList {
+    length: ARGUMENTS.0.len() as c_long,
+    arguments: ARGUMENTS.0.as_ptr(),
+  }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn generate(
+  input: *const c_char,
+  args: CArgumentResultList,
+  progress: ProgressCallback,
+  error: ErrorCallback,
+  cancelled: CancelCallback,
+) -> GeneratorResult {
+  let mut scale = 1.0;
+  for i in 0..args.length {
+    let arg = &*args.results.offset(i as isize);
+    let value = CStr::from_ptr(arg.value).to_string_lossy();
+    match CStr::from_ptr(arg.long).to_bytes() {
+      b"mock-scale" => scale = value.parse().unwrap_or(1.0),
+      b"mock-fail" => error(c"mock generator failed".as_ptr()),
+      _ => {},
+    }
+  }
+
+  // e.g. MOCK_GENERATOR_FAIL_ON=broken fails for every input whose path contains "broken"
+  if let Ok(pattern) = std::env::var("MOCK_GENERATOR_FAIL_ON") {
+    if CStr::from_ptr(input).to_string_lossy().contains(pattern.as_str()) {
+      error(c"mock generator failed".as_ptr());
+    }
+  }
+
+  let mut cuts = Vec::new();
+  for (i, (start, end)) in SEGMENTS.iter().enumerate() {
+    if cancelled() {
+      break;
+    }
+    progress(c"generating".as_ptr(), i as f64 / SEGMENTS.len() as f64);
+    cuts.push(Cut { start: start * scale, end: end * scale });
+  }
+  progress(c"generating".as_ptr(), 1.0);
+
+  // generators are not required to report the length of their input
+  let (len_pre_cut, len_post_cut) = if std::env::var_os("MOCK_GENERATOR_NO_STATS").is_some() {
+    (0.0, 0.0)
+  } else {
+    (DURATION * scale, cuts.iter().map(|cut| cut.end - cut.start).sum())
+  };
+  let cuts = cuts.leak();
+  GeneratorResult {
+    cuts: CutList {
+      length: cuts.len() as c_long,
+      cuts: cuts.as_ptr(),
+    },
+    stats: GeneratorStats {
+      len_pre_cut,
+      len_post_cut,
+    },
+  }
+}
@@ -0,0 +1,129 @@
+//! Deterministic render module used by the integration tests.
+//! Instead of a video it writes a text file listing the input, the cuts and the arguments it received.
+//! It supports audio-only output unless `MOCK_RENDER_VIDEO_ONLY` is set.
+
+use std::ffi::{c_char, c_double, c_long, CStr};
+use std::fmt::Write;
+
+#[repr(C)]
+pub struct Cut {
+  pub start: c_double,
+  pub end: c_double,
+}
+
+#[repr(C)]
+pub struct CutList {
+  pub length: c_long,
+  pub cuts: *const Cut,
+}
+
+#[repr(C)]
+pub struct CArgument {
+  pub short: c_char,
+  pub long: *const c_char,
+  pub description: *const c_char,
+  pub required: bool,
+  pub is_flag: bool,
+}
+
+#[repr(C)]
+pub struct CArgumentList {
+  pub length: c_long,
+  pub arguments: *const CArgument,
+}
+
+#[repr(C)]
+pub struct CArgumentResult {
+  pub long: *const c_char,
+  pub value: *const c_char,
+}
+
+#[repr(C)]
+pub struct CArgumentResultList {
+  pub length: c_long,
+  pub results: *const CArgumentResult,
+}
+
+type ErrorCallback = unsafe extern "C" fn(*const c_char);
+type ProgressCallback = unsafe extern "C" fn(*const c_char, c_double);
+type CancelCallback = unsafe extern "C" fn() -> bool;
+
+struct SyncArguments([CArgument; 1]);
+
+unsafe impl Sync for SyncArguments {}
+
+static ARGUMENTS: SyncArguments = SyncArguments([CArgument {
+  short: b'l' as c_char,
+  long: c"mock-label".as_ptr(),
+  description: c"Text written into the rendered file".as_ptr(),
+  required: false,
+  is_flag: false,
+}]);
+
+#[no_mangle]
+pub extern "C" fn init(_error: ErrorCallback) {}
+
+#[no_mangle]
+pub extern "C" fn version(_error: ErrorCallback) -> *const c_char {
+  c"mock-render 1.0".as_ptr()
+}
+
+#[no_mangle]
+pub extern "C" fn get_arguments(_error: ErrorCallback) -> CArgumentList {
+  CArgumentList {
+    length: ARGUMENTS.0.len() as c_long,
+    arguments: ARGUMENTS.0.as_ptr(),
+  }
+}
+
+#[no_mangle]
+pub extern "C" fn capabilities(_error: ErrorCallback) -> u32 {
+  match std::env::var_os("MOCK_RENDER_VIDEO_ONLY") {
+    Some(_) => 0,
+    None => 1,
+  }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn supports(
+  _container: *const c_char,
+  codecs: *const c_char,
+  output_format: *const c_char,
+  _error: ErrorCallback,
+) -> bool {
+  let codecs = CStr::from_ptr(codecs).to_string_lossy();
+  let output_format = CStr::from_ptr(output_format).to_string_lossy();
+  // e.g. MOCK_RENDER_UNSUPPORTED=mov_text or MOCK_RENDER_UNSUPPORTED=webm
+  match std::env::var("MOCK_RENDER_UNSUPPORTED") {
+    Ok(unsupported) => unsupported != output_format && !codecs.split(',').any(|codec| codec == unsupported),
+    Err(_) => true,
+  }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn render(
+  input: *const c_char,
+  output: *const c_char,
+  cuts: CutList,
+  args: CArgumentResultList,
+  progress: ProgressCallback,
+  error: ErrorCallback,
+  _cancelled: CancelCallback,
+) {
+  let mut content = String::new();
+  let _ = writeln!(content, "input={}", CStr::from_ptr(input).to_string_lossy());
+  for i in 0..cuts.length {
+    let cut = &*cuts.cuts.offset(i as isize);
+    let _ = writeln!(content, "cut={},{}", cut.start, cut.end);
+    progress(c"rendering".as_ptr(), (i + 1) as f64 / cuts.length as f64);
+  }
+  for i in 0..args.length {
+    let arg = &*args.results.offset(i as isize);
+    let _ = writeln!(content, "arg={}={}", CStr::from_ptr(arg.long).to_string_lossy(), CStr::from_ptr(arg.value).to_string_lossy());
+  }
+
+  let output = CStr::from_ptr(output).to_string_lossy().to_string();
+  if std::fs::write(output, content).is_err() {
+    error(c"mock render could not write the output".as_ptr());
+  }
+}